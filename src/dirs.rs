@@ -1,17 +1,72 @@
 use std::{
-    collections::HashMap,
-    error::Error,
-    ffi::OsStr,
-    fmt::Display,
-    path::{Path, PathBuf},
+    ffi::{OsStr, OsString},
+    path::{Component, Path, PathBuf},
 };
 
+mod args;
+mod canonical;
+mod dir;
+mod env;
+mod expand;
+mod layered;
+mod layout;
+mod partial;
+mod physical;
+mod rebase;
+mod relocate;
+mod staged;
+
 #[cfg(feature = "serde")]
 mod serde;
 
+pub use args::{ArgError, ArgErrorKind};
+pub use canonical::{
+    CanonicalInstallDirs, CanonicalizationError, CanonicalizationErrorKind, CanonicalizationIssue,
+};
+pub use dir::Dir;
+pub use env::{EnvFile, EnvNaming, EnvSource, FnEnv, ProcessEnv};
+pub use expand::{ExpandError, ExpandErrorKind, ExpandMode};
+pub use layered::{InstallDirsBuilder, Layer, LayeredDirs, Provenance};
+pub use layout::{Layout, LayoutContext, LayoutPolicy, LibdirConvention};
+pub use partial::PartialInstallDirs;
+pub use physical::PhysicalDirs;
+pub use rebase::Rebased;
+pub use staged::StagedDirs;
+
 ///
 /// Struct containing all known Install directories
-#[derive(Clone, Debug)]
+///
+/// With the `serde` feature, each directory is serialized as a string if it is valid UTF-8, and as bytes otherwise,
+///  so paths which are not valid UTF-8 are preserved by both self-describing and binary formats.
+/// `destdir` and `sysroot` are serialized in the same way, and are omitted by self-describing formats if they are not set.
+///
+/// ## Example
+///
+/// ```
+/// # #[cfg(all(feature = "serde", unix))] {
+/// use install_dirs::dirs::InstallDirs;
+/// use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+/// let mut dirs = InstallDirs::with_project_name("foo");
+/// dirs.prefix = OsStr::from_bytes(b"/opt/f\xF6\xF6").into();
+///
+/// let bytes = bincode::serialize(&dirs).unwrap();
+/// assert_eq!(bincode::deserialize::<InstallDirs>(&bytes).unwrap(), dirs);
+///
+/// let json = serde_json::to_string(&dirs).unwrap();
+/// assert!(!json.contains("destdir") && !json.contains("sysroot"));
+/// assert_eq!(serde_json::from_str::<InstallDirs>(&json).unwrap(), dirs);
+///
+/// dirs.destdir = Some("/tmp/stage".into());
+/// dirs.sysroot = Some("/opt/sysroots/aarch64".into());
+/// let bytes = bincode::serialize(&dirs).unwrap();
+/// assert_eq!(bincode::deserialize::<InstallDirs>(&bytes).unwrap(), dirs);
+/// let json = serde_json::to_string(&dirs).unwrap();
+/// assert!(json.contains(r#""destdir":"/tmp/stage""#));
+/// assert!(json.contains(r#""sysroot":"/opt/sysroots/aarch64""#));
+/// assert_eq!(serde_json::from_str::<InstallDirs>(&json).unwrap(), dirs);
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 #[non_exhaustive]
 pub struct InstallDirs {
     pub prefix: PathBuf,
@@ -21,38 +76,148 @@ pub struct InstallDirs {
     pub libdir: PathBuf,
     pub libexecdir: PathBuf,
     pub includedir: PathBuf,
+    pub oldincludedir: PathBuf,
     pub datarootdir: PathBuf,
     pub datadir: PathBuf,
     pub mandir: PathBuf,
+    pub man1dir: PathBuf,
+    pub man2dir: PathBuf,
+    pub man3dir: PathBuf,
+    pub man4dir: PathBuf,
+    pub man5dir: PathBuf,
+    pub man6dir: PathBuf,
+    pub man7dir: PathBuf,
+    pub man8dir: PathBuf,
+    pub man9dir: PathBuf,
+    /// The extension used for installed man pages (not a directory), `.1` by default
+    pub manext: PathBuf,
     pub docdir: PathBuf,
+    pub htmldir: PathBuf,
+    pub dvidir: PathBuf,
+    pub pdfdir: PathBuf,
+    pub psdir: PathBuf,
     pub infodir: PathBuf,
     pub localedir: PathBuf,
+    pub lispdir: PathBuf,
     pub localstatedir: PathBuf,
     pub runstatedir: PathBuf,
     pub sharedstatedir: PathBuf,
     pub sysconfdir: PathBuf,
+    /// The package-specific subdirectory of `datadir`, as defined by automake.
+    /// Without a project name this is empty, so it resolves to `datadir` itself; with one it is the project name, so it resolves to `<datadir>/<name>`,
+    ///  see [`InstallDirs::defaults`].
+    pub pkgdatadir: PathBuf,
+    /// The package-specific subdirectory of `libdir`, as defined by automake.
+    /// Without a project name this is empty, so it resolves to `libdir` itself; with one it is the project name, so it resolves to `<libdir>/<name>`,
+    ///  see [`InstallDirs::defaults`].
+    pub pkglibdir: PathBuf,
+    /// The package-specific subdirectory of `includedir`, as defined by automake.
+    /// Without a project name this is empty, so it resolves to `includedir` itself; with one it is the project name, so it resolves to `<includedir>/<name>`,
+    ///  see [`InstallDirs::defaults`].
+    pub pkgincludedir: PathBuf,
+    /// The package-specific subdirectory of `libexecdir`, as defined by automake.
+    /// Without a project name this is empty, so it resolves to `libexecdir` itself; with one it is the project name, so it resolves to `<libexecdir>/<name>`,
+    ///  see [`InstallDirs::defaults`].
+    pub pkglibexecdir: PathBuf,
+    /// The staging root files are installed under, as given by `DESTDIR` in a Makefile.
+    /// This is not one of the directories named by [`Dir`], is not canonicalized, and does not affect the paths used at run time,
+    ///  see [`CanonicalInstallDirs::staged`].
+    pub destdir: Option<PathBuf>,
+    /// The root of the target system when cross-compiling, as given by `PKG_CONFIG_SYSROOT_DIR` or the argument to `--with-sysroot`.
+    /// Files of packages already installed for the target are found under it, while the paths used at run time are unaffected,
+    ///  see [`CanonicalInstallDirs::in_sysroot`].
+    pub sysroot: Option<PathBuf>,
 }
 
-#[derive(Debug)]
-pub struct CanonicalizationError {
-    prefix: PathBuf,
+///
+/// A value which can be set by a `configure` option or an environment variable:
+///  one of the directories, or one of the fields of [`InstallDirs`] which is not a directory
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+enum Setting {
+    Dir(Dir),
+    Destdir,
+    Sysroot,
 }
 
-impl Display for CanonicalizationError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str("Failed to canonicalize Install Dirs ")?;
-        f.write_fmt(format_args!(
-            "(prefix {} is not an absolute path)",
-            self.prefix.display()
-        ))
+///
+/// The environment variables which set the fields of [`InstallDirs`] which are not directories, regardless of the [`EnvNaming`]
+const SETTING_VARS: &[(Setting, &str)] = &[
+    (Setting::Destdir, "DESTDIR"),
+    (Setting::Sysroot, "PKG_CONFIG_SYSROOT_DIR"),
+];
+
+///
+/// The `configure` options which set the fields of [`InstallDirs`] which are not directories.
+/// Unlike the directory options, these must be spelled in full.
+const SETTING_OPTIONS: &[(Setting, &str)] = &[(Setting::Sysroot, "--with-sysroot")];
+
+impl Setting {
+    ///
+    /// Finds the setting named by the `configure` option `key`, which is either a directory option (see [`Dir::from_option`])
+    ///  or one of [`SETTING_OPTIONS`]
+    fn from_option(key: &str) -> Option<Setting> {
+        Dir::from_option(key).map(Setting::Dir).or_else(|| {
+            SETTING_OPTIONS
+                .iter()
+                .find(|&&(_, option)| option == key)
+                .map(|&(setting, _)| setting)
+        })
     }
 }
 
-impl Error for CanonicalizationError {}
+///
+/// Returns each setting in `env` named according to `naming`, the variable it was read from, and its value.
+/// Directories are read if they are set, and the variables in [`SETTING_VARS`] if they are set and not empty.
+fn env_settings<E: EnvSource + ?Sized>(
+    env: &E,
+    naming: &EnvNaming,
+) -> Vec<(Setting, String, OsString)> {
+    let dirs = Dir::ALL.iter().filter_map(|&dir| {
+        let var = naming.var_name(dir)?;
+        let val = env.var_os(&var)?;
+        Some((Setting::Dir(dir), var, val))
+    });
+    let others = SETTING_VARS.iter().filter_map(|&(setting, var)| {
+        let val = env.var_os(var).filter(|val| !val.is_empty())?;
+        Some((setting, var.to_string(), val))
+    });
+    dirs.chain(others).collect()
+}
+
+///
+/// How canonicalization treats a `..` component which would go above the root directory, such as in `/usr/../..`
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub enum ParentDirs {
+    /// Return an error
+    #[default]
+    Reject,
+    /// Stay at the root directory, as the kernel does when resolving `/..`
+    Clamp,
+}
 
 impl InstallDirs {
     ///
+    /// Returns the default install directories, as specified by the GNU Coding Standards.
+    /// Each directory other than `prefix` (and `oldincludedir`) is relative to the directory it is derived from,
+    ///  and is resolved by [`InstallDirs::canonicalize`].
+    ///
+    /// **No project name is set**, so the package directories (`pkgdatadir`, `pkglibdir`, `pkgincludedir` and `pkglibexecdir`) are empty,
+    ///  and canonicalize to the shared directories they are derived from, such as `/usr/local/lib` for `pkglibdir`.
+    /// Installing package files to them would put them straight into the shared directories.
+    /// Use [`InstallDirs::with_project_name`] or [`InstallDirs::set_project_name`] before using the package directories.
+    ///
+    /// ## Example
     ///
+    /// ```
+    /// use install_dirs::dirs::InstallDirs;
+    /// let dirs = InstallDirs::defaults().canonicalize().unwrap();
+    /// assert_eq!(dirs.pkglibdir(), dirs.libdir());
+    ///
+    /// let mut dirs = InstallDirs::defaults();
+    /// dirs.set_project_name("foo");
+    /// let dirs = dirs.canonicalize().unwrap();
+    /// assert_eq!(dirs.pkglibdir(), dirs.libdir().join("foo"));
+    /// ```
     pub fn defaults() -> Self {
         Self {
             prefix: if cfg!(windows) {
@@ -67,19 +232,49 @@ impl InstallDirs {
             libdir: "lib".into(),
             libexecdir: "libexec".into(),
             includedir: "include".into(),
+            oldincludedir: "/usr/include".into(),
             datarootdir: "share".into(),
             datadir: "".into(),
             mandir: "man".into(),
+            man1dir: "man1".into(),
+            man2dir: "man2".into(),
+            man3dir: "man3".into(),
+            man4dir: "man4".into(),
+            man5dir: "man5".into(),
+            man6dir: "man6".into(),
+            man7dir: "man7".into(),
+            man8dir: "man8".into(),
+            man9dir: "man9".into(),
+            manext: ".1".into(),
             docdir: "doc".into(),
+            htmldir: "".into(),
+            dvidir: "".into(),
+            pdfdir: "".into(),
+            psdir: "".into(),
             infodir: "info".into(),
             localedir: "locale".into(),
+            lispdir: "emacs/site-lisp".into(),
             localstatedir: "var".into(),
             runstatedir: "run".into(),
             sharedstatedir: "com".into(),
             sysconfdir: "etc".into(),
+            pkgdatadir: "".into(),
+            pkglibdir: "".into(),
+            pkgincludedir: "".into(),
+            pkglibexecdir: "".into(),
+            destdir: None,
+            sysroot: None,
         }
     }
 
+    ///
+    /// Returns the default install directories used with `layout`.
+    /// This is the same as [`InstallDirs::defaults`], except for [`Layout::Meson`],
+    ///  where the directories Meson defines are relative to `prefix` and `libdir` is given by its [`LibdirConvention`].
+    pub fn defaults_for<P: LayoutPolicy + ?Sized>(layout: &P) -> Self {
+        layout.defaults()
+    }
+
     pub fn with_project_name<S: AsRef<OsStr> + ?Sized>(name: &S) -> Self {
         Self {
             prefix: if cfg!(windows) {
@@ -90,54 +285,24 @@ impl InstallDirs {
             } else {
                 "/usr/local".into()
             },
-            exec_prefix: "".into(),
-            bindir: "bin".into(),
-            sbindir: "sbin".into(),
-            libdir: "lib".into(),
-            libexecdir: "libexec".into(),
-            includedir: "include".into(),
-            datarootdir: "share".into(),
-            datadir: "".into(),
-            mandir: "man".into(),
             docdir: {
                 let mut path = PathBuf::new();
                 path.push("doc");
                 path.push(name.as_ref());
                 path
             },
-            infodir: "info".into(),
-            localedir: "locale".into(),
-            localstatedir: "var".into(),
-            runstatedir: "run".into(),
-            sharedstatedir: "com".into(),
-            sysconfdir: "etc".into(),
+            pkgdatadir: name.as_ref().into(),
+            pkglibdir: name.as_ref().into(),
+            pkgincludedir: name.as_ref().into(),
+            pkglibexecdir: name.as_ref().into(),
+            ..Self::defaults()
         }
     }
 
     pub fn with_exec_target<S: AsRef<OsStr>>(target: &S) -> Self {
         Self {
-            prefix: if cfg!(windows) {
-                "C:\\Program Files\\"
-            } else {
-                "/usr/local"
-            }
-            .into(),
             exec_prefix: target.as_ref().into(),
-            bindir: "bin".into(),
-            sbindir: "sbin".into(),
-            libdir: "lib".into(),
-            libexecdir: "libexec".into(),
-            includedir: "include".into(),
-            datarootdir: "share".into(),
-            datadir: "".into(),
-            mandir: "man".into(),
-            docdir: "doc".into(),
-            infodir: "info".into(),
-            localedir: "locale".into(),
-            localstatedir: "var".into(),
-            runstatedir: "run".into(),
-            sharedstatedir: "com".into(),
-            sysconfdir: "etc".into(),
+            ..Self::defaults()
         }
     }
 
@@ -146,35 +311,8 @@ impl InstallDirs {
         target: &T,
     ) -> Self {
         Self {
-            prefix: if cfg!(windows) {
-                let mut buf = PathBuf::new();
-                buf.push("C:\\Program Files");
-                buf.push(name.as_ref());
-                buf
-            } else {
-                "/usr/local".into()
-            },
             exec_prefix: target.as_ref().into(),
-            bindir: "bin".into(),
-            sbindir: "sbin".into(),
-            libdir: "lib".into(),
-            libexecdir: "libexec".into(),
-            includedir: "include".into(),
-            datarootdir: "share".into(),
-            datadir: "".into(),
-            mandir: "man".into(),
-            docdir: {
-                let mut path = PathBuf::new();
-                path.push("doc");
-                path.push(name.as_ref());
-                path
-            },
-            infodir: "info".into(),
-            localedir: "locale".into(),
-            localstatedir: "var".into(),
-            runstatedir: "run".into(),
-            sharedstatedir: "com".into(),
-            sysconfdir: "etc".into(),
+            ..Self::with_project_name(name)
         }
     }
 
@@ -184,196 +322,237 @@ impl InstallDirs {
         }
 
         self.docdir.push(name.as_ref());
+        self.pkgdatadir.push(name.as_ref());
+        self.pkglibdir.push(name.as_ref());
+        self.pkgincludedir.push(name.as_ref());
+        self.pkglibexecdir.push(name.as_ref());
     }
 
-    pub fn set_from_arg(&mut self, key: &str, val: String) -> Result<(), ()> {
-        match key {
-            "--prefix" => self.prefix = PathBuf::from(val),
-            "--exec-prefix" => self.exec_prefix = PathBuf::from(val),
-            "--bindir" => self.bindir = PathBuf::from(val),
-            "--sbindir" => self.sbindir = PathBuf::from(val),
-            "--libdir" => self.libdir = PathBuf::from(val),
-            "--libexecdir" => self.libexecdir = PathBuf::from(val),
-            "--includedir" => self.includedir = PathBuf::from(val),
-            "--datarootdir" => self.datarootdir = PathBuf::from(val),
-            "--datadir" => self.datadir = PathBuf::from(val),
-            "--mandir" => self.mandir = PathBuf::from(val),
-            "--docdir" => self.docdir = PathBuf::from(val),
-            "--infodir" => self.infodir = PathBuf::from(val),
-            "--localedir" => self.localedir = PathBuf::from(val),
-            "--localstatedir" => self.localstatedir = PathBuf::from(val),
-            "--runstatedir" => self.runstatedir = PathBuf::from(val),
-            "--sharedstatedir" => self.sharedstatedir = PathBuf::from(val),
-            "--sysconfdir" => self.sysconfdir = PathBuf::from(val),
-            _ => return Err(()),
-        }
+    ///
+    /// Sets the directory named by the `configure` option `key` (such as `--libdir`) to `val`.
+    /// `--with-sysroot` sets [`InstallDirs::sysroot`], as with [`InstallDirs::parse_args`].
+    ///
+    /// Returns an error of kind [`ArgErrorKind::Unknown`] if `key` does not name a directory,
+    ///  which suggests the closest known option if `key` looks like a typo.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use install_dirs::dirs::InstallDirs;
+    /// let mut dirs = InstallDirs::defaults();
+    /// dirs.set_from_arg("--with-sysroot", "/opt/sysroots/aarch64").unwrap();
+    /// assert_eq!(dirs.sysroot.as_ref().unwrap().to_str(), Some("/opt/sysroots/aarch64"));
+    ///
+    /// let err = dirs.set_from_arg("--libexcdir", "lib").unwrap_err();
+    /// assert_eq!(err.suggestion(), Some("--libexecdir"));
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     "unknown option `--libexcdir`; did you mean `--libexecdir`?"
+    /// );
+    /// ```
+    pub fn set_from_arg<S: Into<OsString>>(&mut self, key: &str, val: S) -> Result<(), ArgError> {
+        let setting = Setting::from_option(key).ok_or_else(|| ArgError::unknown(key))?;
+        self.set_setting(setting, PathBuf::from(val.into()));
 
         Ok(())
     }
 
-    pub fn canonicalize(mut self) -> Result<Self, CanonicalizationError> {
-        if !self.prefix.has_root() {
-            Err(CanonicalizationError {
-                prefix: self.prefix,
-            })
-        } else {
-            if !self.exec_prefix.has_root() {
-                self.exec_prefix = {
-                    let mut path = PathBuf::new();
-                    path.push(self.prefix.clone());
-                    path.push(self.exec_prefix);
-                    path
-                }
-            }
-
-            let exec_prefix = self.exec_prefix.clone();
-            let data_prefix = if (&*self.prefix) == Path::new("/") {
-                let mut data_prefix = PathBuf::new();
-                data_prefix.push("/usr");
-                data_prefix
-            } else {
-                self.prefix.clone()
-            };
-            let state_prefix = if self.prefix.starts_with("/usr") {
-                let mut prefix = PathBuf::new();
-                prefix.push("/");
-                prefix
-            } else {
-                self.prefix.clone()
-            };
-            if !self.bindir.has_root() {
-                self.bindir = {
-                    let mut path = exec_prefix.clone();
-                    path.push(self.bindir);
-                    path
-                };
-            }
-
-            if !self.sbindir.has_root() {
-                self.sbindir = {
-                    let mut path = exec_prefix.clone();
-                    path.push(self.sbindir);
-                    path
-                };
-            }
-
-            if !self.libdir.has_root() {
-                self.libdir = {
-                    let mut path = exec_prefix.clone();
-                    path.push(self.libdir);
-                    path
-                };
-            }
-
-            if !self.libexecdir.has_root() {
-                self.libexecdir = {
-                    let mut path = exec_prefix.clone();
-                    path.push(self.libexecdir);
-                    path
-                };
-            }
-
-            if !self.includedir.has_root() {
-                self.includedir = {
-                    let mut path = exec_prefix.clone();
-                    path.push(self.includedir);
-                    path
-                };
-            }
-
-            if !self.datarootdir.has_root() {
-                self.datarootdir = {
-                    let mut path = data_prefix.clone();
-                    path.push(self.datarootdir);
-                    path
-                };
-            }
-
-            if !self.datadir.has_root() {
-                self.datadir = {
-                    let mut path = self.datarootdir.clone();
-                    path.push(self.datadir);
-                    path
-                };
-            }
+    ///
+    /// Resolves each relative directory against the directory it is derived from, producing a [`CanonicalInstallDirs`]
+    ///  in which every directory (other than `manext`) is an absolute path.
+    /// This uses the rules described by [`Layout::Default`], see [`InstallDirs::canonicalize_with`].
+    ///
+    /// Returns an error if `prefix` is not an absolute path, if directories depend on each other in a cycle,
+    ///  or if a directory goes above the root directory.
+    pub fn canonicalize(self) -> Result<CanonicalInstallDirs, CanonicalizationError> {
+        self.canonicalize_with(&Layout::Default)
+    }
 
-            if !self.mandir.has_root() {
-                self.mandir = {
-                    let mut path = self.datarootdir.clone();
-                    path.push(self.mandir);
-                    path
-                };
-            }
+    ///
+    /// Resolves each relative directory according to the rules of `layout` (see [`LayoutPolicy`]), producing a [`CanonicalInstallDirs`]
+    ///  in which every directory (other than `manext`) is an absolute path.
+    ///
+    /// A value may refer to other directories, written as `${name}` or `$(name)` as in autoconf, such as `${exec_prefix}/lib64`.
+    /// Each reference is replaced by the canonical value of that directory before the value is resolved,
+    ///  and the original value remains available from [`CanonicalInstallDirs::unexpanded`].
+    /// Any other reference, including one without braces such as `$prefix`, is an error rather than being kept in the result.
+    ///
+    /// Each directory is then normalized lexically, without accessing the filesystem:
+    ///  `.` components are removed, and each `..` component removes the component before it, so `/usr/local/../lib64` becomes `/usr/lib64`.
+    ///
+    /// Returns an error if `prefix` is not an absolute path, if directories depend on each other in a cycle,
+    ///  if a directory goes above the root directory (see [`InstallDirs::canonicalize_with_mode`] to clamp it instead),
+    ///  if a value contains a NUL byte or a reference to anything other than a directory,
+    ///  if `layout` does not permit the value of a directory that is already absolute (see [`LayoutPolicy::permits`]),
+    ///  or if `layout` resolves a directory to a path which is not absolute.
+    /// Every directory is checked, and the error lists each problem that was found (see [`CanonicalizationError::issues`]).
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use install_dirs::dirs::{CanonicalizationErrorKind, Dir, InstallDirs, Layout};
+    /// let mut dirs = InstallDirs::defaults();
+    /// dirs.prefix = "/usr".into();
+    /// let gnu = dirs.clone().canonicalize_with(&Layout::Gnu).unwrap();
+    /// assert_eq!(gnu.sysconfdir().to_str(), Some("/usr/etc"));
+    /// assert_eq!(gnu.runstatedir().to_str(), Some("/usr/var/run"));
+    /// let default = dirs.clone().canonicalize().unwrap();
+    /// assert_eq!(default.sysconfdir().to_str(), Some("/etc"));
+    ///
+    /// dirs.libdir = "${exec_prefix}/lib64".into();
+    /// let canonical = dirs.clone().canonicalize_with(&Layout::Gnu).unwrap();
+    /// assert_eq!(canonical.libdir().to_str(), Some("/usr/lib64"));
+    /// assert_eq!(canonical.unexpanded(Dir::Libdir).to_str(), Some("${exec_prefix}/lib64"));
+    ///
+    /// dirs.bindir = "./bin/../sbin".into();
+    /// let canonical = dirs.clone().canonicalize_with(&Layout::Gnu).unwrap();
+    /// assert_eq!(canonical.bindir().to_str(), Some("/usr/sbin"));
+    ///
+    /// dirs.datadir = "${nonexistent}/data".into();
+    /// let err = dirs.clone().canonicalize_with(&Layout::Gnu).unwrap_err();
+    /// assert_eq!(
+    ///     err.issues()[0].kind(),
+    ///     &CanonicalizationErrorKind::UnknownReference("${nonexistent}".to_string())
+    /// );
+    /// dirs.datadir = "$prefix/share".into();
+    /// dirs.infodir = "${manext}/info".into();
+    /// let err = dirs.clone().canonicalize_with(&Layout::Gnu).unwrap_err();
+    /// let references: Vec<_> = err.issues().iter().map(|issue| issue.kind().clone()).collect();
+    /// assert_eq!(
+    ///     references,
+    ///     [
+    ///         CanonicalizationErrorKind::UnknownReference("$prefix".to_string()),
+    ///         CanonicalizationErrorKind::UnknownReference("${manext}".to_string()),
+    ///     ]
+    /// );
+    /// dirs.datadir = "${datarootdir}".into();
+    /// dirs.infodir = "${datarootdir}/info".into();
+    ///
+    /// dirs.exec_prefix = "${bindir}/..".into();
+    /// assert!(dirs.canonicalize_with(&Layout::Gnu).is_err());
+    /// ```
+    pub fn canonicalize_with<P: LayoutPolicy + ?Sized>(
+        self,
+        layout: &P,
+    ) -> Result<CanonicalInstallDirs, CanonicalizationError> {
+        self.canonicalize_with_mode(layout, ParentDirs::Reject)
+    }
 
-            if !self.infodir.has_root() {
-                self.infodir = {
-                    let mut path = self.datarootdir.clone();
-                    path.push(self.infodir);
-                    path
-                };
-            }
-            if !self.docdir.has_root() {
-                self.docdir = {
-                    let mut path = self.datarootdir.clone();
-                    path.push(self.docdir);
-                    path
-                };
+    ///
+    /// Canonicalizes according to `layout` like [`InstallDirs::canonicalize_with`],
+    ///  treating a `..` component which would go above the root directory according to `parent_dirs`.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use install_dirs::dirs::{InstallDirs, Layout, ParentDirs};
+    /// let mut dirs = InstallDirs::defaults();
+    /// dirs.prefix = "/usr".into();
+    /// dirs.libdir = "../../../lib".into();
+    /// assert!(dirs.clone().canonicalize_with_mode(&Layout::Gnu, ParentDirs::Reject).is_err());
+    /// let clamped = dirs.canonicalize_with_mode(&Layout::Gnu, ParentDirs::Clamp).unwrap();
+    /// assert_eq!(clamped.libdir().to_str(), Some("/lib"));
+    /// ```
+    pub fn canonicalize_with_mode<P: LayoutPolicy + ?Sized>(
+        self,
+        layout: &P,
+        parent_dirs: ParentDirs,
+    ) -> Result<CanonicalInstallDirs, CanonicalizationError> {
+        let mut issues = Vec::new();
+        let (order, cycles) = layout::resolution_order(layout, &self);
+        // Directories in a cycle, and those which depend on them, cannot be resolved to absolute paths
+        let mut unresolvable: Vec<Dir> = cycles.iter().flatten().copied().collect();
+        for cycle in cycles {
+            issues.push(CanonicalizationIssue::new(
+                cycle[0],
+                self.get(cycle[0]),
+                CanonicalizationErrorKind::Cycle(cycle),
+            ));
+        }
+
+        for (dir, val) in self.iter() {
+            if val.as_os_str().as_encoded_bytes().contains(&0) {
+                issues.push(CanonicalizationIssue::new(
+                    dir,
+                    val,
+                    CanonicalizationErrorKind::ContainsNul,
+                ));
             }
-
-            if !self.localedir.has_root() {
-                self.localedir = {
-                    let mut path = self.datarootdir.clone();
-                    path.push(self.localedir);
-                    path
-                };
+            if dir.is_path() {
+                for reference in expand::unknown_references(val.as_os_str()) {
+                    issues.push(CanonicalizationIssue::new(
+                        dir,
+                        val,
+                        CanonicalizationErrorKind::UnknownReference(reference),
+                    ));
+                }
             }
+        }
 
-            if !self.sharedstatedir.has_root() {
-                self.sharedstatedir = {
-                    let mut path = data_prefix.clone();
-                    path.push(self.sharedstatedir);
-                    path
-                };
+        let mut raw = self.clone();
+        let mut dirs = self.clone();
+        let mut relative_prefix = false;
+        for dir in order {
+            if expand::dir_references(self.get(dir).as_os_str())
+                .into_iter()
+                .chain(layout.base(dir))
+                .any(|dep| unresolvable.contains(&dep))
+            {
+                unresolvable.push(dir);
             }
-
-            if !self.sysconfdir.has_root() {
-                self.sysconfdir = if state_prefix.starts_with("/opt") {
-                    let mut path = PathBuf::new();
-                    path.push("/");
-                    path.push(self.sysconfdir);
-                    path.push(state_prefix.clone());
-                    path
-                } else {
-                    let mut path = state_prefix.clone();
-                    path.push(self.sysconfdir);
-                    path
-                }
+            let val = PathBuf::from(expand::expand_dir_references(
+                self.get(dir).as_os_str(),
+                &dirs,
+            ));
+            if dir == Dir::Prefix && !val.is_absolute() {
+                relative_prefix = true;
+                issues.push(CanonicalizationIssue::new(
+                    dir,
+                    self.get(dir),
+                    CanonicalizationErrorKind::RelativePrefix,
+                ));
             }
-
-            if !self.localstatedir.has_root() {
-                self.localstatedir = if state_prefix.starts_with("/opt") {
-                    let mut path = PathBuf::new();
-                    path.push("/");
-                    path.push(self.localstatedir);
-                    path.push(state_prefix.clone());
-                    path
-                } else {
-                    let mut path = state_prefix.clone();
-                    path.push(self.localstatedir);
-                    path
+            let resolved = if val.is_absolute() {
+                raw.set(dir, val.clone());
+                val
+            } else {
+                raw.set(dir, val);
+                layout.resolve(dir, &LayoutContext::new(&raw, &dirs))
+            };
+            let resolved = match normalize(&resolved, parent_dirs) {
+                Some(normalized) => normalized,
+                None => {
+                    issues.push(CanonicalizationIssue::new(
+                        dir,
+                        self.get(dir),
+                        CanonicalizationErrorKind::EscapesRoot(resolved.clone()),
+                    ));
+                    normalize(&resolved, ParentDirs::Clamp).unwrap_or(resolved)
                 }
+            };
+            // Every directory is relative to a relative prefix, which has already been reported, as have cycles
+            if !resolved.is_absolute() && !relative_prefix && !unresolvable.contains(&dir) {
+                issues.push(CanonicalizationIssue::new(
+                    dir,
+                    self.get(dir),
+                    CanonicalizationErrorKind::NotAbsolute(resolved.clone()),
+                ));
             }
-
-            if !self.sharedstatedir.has_root() {
-                self.sharedstatedir = {
-                    let mut path = self.localstatedir.clone();
-                    path.push(self.sharedstatedir);
-                    path
-                };
+            if raw.get(dir).is_absolute() && !layout.permits(dir, &resolved, &dirs.prefix) {
+                issues.push(CanonicalizationIssue::new(
+                    dir,
+                    self.get(dir),
+                    CanonicalizationErrorKind::OutsidePrefix(dirs.prefix.clone()),
+                ));
             }
+            dirs.set(dir, resolved);
+        }
 
-            Ok(self)
+        if !issues.is_empty() {
+            issues.sort_by_key(|issue| issue.dir());
+            return Err(CanonicalizationError::new(issues));
         }
+        Ok(CanonicalInstallDirs::new(dirs, self))
     }
 
     pub fn canonicalize_dir<S: AsRef<OsStr> + ?Sized, T: Into<PathBuf>>(
@@ -391,78 +570,61 @@ impl InstallDirs {
         dir
     }
 
+    ///
+    /// Reads each directory from the environment variable named by [`Dir::env_name`], if it is set,
+    ///  [`InstallDirs::destdir`] from `DESTDIR`, and [`InstallDirs::sysroot`] from `PKG_CONFIG_SYSROOT_DIR`,
+    ///  if they are set and not empty.
+    /// Values are read as [`OsString`]s, so paths which are not valid UTF-8 are preserved.
     pub fn read_env(&mut self) {
-        if let Ok(dir) = std::env::var("prefix") {
-            self.prefix = dir.into()
-        }
-
-        if let Ok(dir) = std::env::var("exec_prefix") {
-            self.exec_prefix = dir.into()
-        }
-
-        if let Ok(dir) = std::env::var("bindir") {
-            self.bindir = dir.into()
-        }
-
-        if let Ok(dir) = std::env::var("libdir") {
-            self.libdir = dir.into()
-        }
-
-        if let Ok(dir) = std::env::var("sbindir") {
-            self.sbindir = dir.into()
-        }
-        if let Ok(dir) = std::env::var("libexecdir") {
-            self.libexecdir = dir.into()
-        }
-        if let Ok(dir) = std::env::var("includedir") {
-            self.includedir = dir.into()
-        }
-
-        if let Ok(dir) = std::env::var("datarootdir") {
-            self.datarootdir = dir.into()
-        }
-
-        if let Ok(dir) = std::env::var("datadir") {
-            self.datadir = dir.into()
-        }
-
-        if let Ok(dir) = std::env::var("mandir") {
-            self.mandir = dir.into()
-        }
-
-        if let Ok(dir) = std::env::var("docdir") {
-            self.docdir = dir.into()
-        }
-
-        if let Ok(dir) = std::env::var("infodir") {
-            self.infodir = dir.into()
-        }
-
-        if let Ok(dir) = std::env::var("localedir") {
-            self.localedir = dir.into()
-        }
-
-        if let Ok(dir) = std::env::var("sharedstatedir") {
-            self.sharedstatedir = dir.into()
-        }
+        self.read_env_from(&ProcessEnv)
+    }
 
-        if let Ok(dir) = std::env::var("localstatedir") {
-            self.localstatedir = dir.into()
-        }
+    ///
+    /// Reads each directory from the variable named by [`Dir::env_name`] in `env`, if it is set.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use install_dirs::dirs::InstallDirs;
+    /// use std::collections::HashMap;
+    /// let mut env = HashMap::new();
+    /// env.insert("prefix", "/opt/foo");
+    /// let mut dirs = InstallDirs::defaults();
+    /// dirs.read_env_from(&env);
+    /// assert_eq!(dirs.prefix.to_str(), Some("/opt/foo"));
+    /// ```
+    pub fn read_env_from<E: EnvSource + ?Sized>(&mut self, env: &E) {
+        self.read_env_named(env, &EnvNaming::Gnu)
+    }
 
-        if let Ok(dir) = std::env::var("runstatedir") {
-            self.runstatedir = dir.into()
+    ///
+    /// Reads each directory from the variable in `env` given by `naming`, if it is set.
+    /// Directories which have no name in `naming` are left unchanged.
+    ///
+    /// Regardless of `naming`, [`InstallDirs::destdir`] is read from `DESTDIR`, and [`InstallDirs::sysroot`] from `PKG_CONFIG_SYSROOT_DIR`,
+    ///  if they are set and not empty.
+    /// Other variables which name a sysroot, such as `SYSROOT`, are too generic to be read implicitly; set [`InstallDirs::sysroot`] from them explicitly.
+    pub fn read_env_named<E: EnvSource + ?Sized>(&mut self, env: &E, naming: &EnvNaming) {
+        for (setting, _, val) in env_settings(env, naming) {
+            self.set_setting(setting, val.into());
         }
+    }
 
-        if let Ok(dir) = std::env::var("sysconfdir") {
-            self.sysconfdir = dir.into()
+    ///
+    /// Sets `setting` to `val`
+    fn set_setting(&mut self, setting: Setting, val: PathBuf) {
+        match setting {
+            Setting::Dir(dir) => self.set(dir, val),
+            Setting::Destdir => self.destdir = Some(val),
+            Setting::Sysroot => self.sysroot = Some(val),
         }
     }
 
     ///
     /// Obtains an iterator suitable for passing to [`std::process::Command::envs`].
     /// The resulting iterator contains each field and the value of that field.
-    /// The order which the Items are encounted is unspecified
+    /// The order which the Items are encounted is unspecified.
+    /// Values are yielded as [`OsStr`]s, so paths which are not valid UTF-8 are passed through unchanged.
     ///
     /// ## Example
     ///
@@ -479,25 +641,27 @@ impl InstallDirs {
     ///     .spawn()
     ///     .expect("printenv failed to start");
     /// ```
-    pub fn as_env(&self) -> impl IntoIterator<Item = (&str, &Path)> {
-        let mut map = HashMap::new();
-        map.insert("prefix", &*self.prefix);
-        map.insert("exec_prefix", &*self.exec_prefix);
-        map.insert("bindir", &*self.bindir);
-        map.insert("sbindir", &*self.sbindir);
-        map.insert("libdir", &*self.libdir);
-        map.insert("libexecdir", &*self.libexecdir);
-        map.insert("datarootdir", &*self.datarootdir);
-        map.insert("datadir", &*self.datadir);
-        map.insert("docdir", &*self.docdir);
-        map.insert("mandir", &*self.mandir);
-        map.insert("infodir", &*self.infodir);
-        map.insert("localedir", &*self.localedir);
-        map.insert("sharedstatedir", &*self.sharedstatedir);
-        map.insert("localstatedir", &*self.localstatedir);
-        map.insert("runstatedir", &*self.runstatedir);
-        map.insert("sysconfdir", &*self.sysconfdir);
-        map
+    pub fn as_env(&self) -> impl IntoIterator<Item = (&str, &OsStr)> {
+        self.iter()
+            .map(|(dir, val)| (dir.env_name(), val.as_os_str()))
+    }
+
+    ///
+    /// Obtains an iterator like [`InstallDirs::as_env`], but with each variable named according to `naming`.
+    /// Directories which have no name in `naming` are skipped.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use install_dirs::dirs::{EnvNaming, InstallDirs};
+    /// let dirs = InstallDirs::defaults();
+    /// let env: Vec<_> = dirs.as_env_named(&EnvNaming::prefixed("MYAPP")).into_iter().collect();
+    /// assert!(env.iter().any(|(name, val)| name == "MYAPP_LIBDIR" && *val == "lib"));
+    /// ```
+    pub fn as_env_named(&self, naming: &EnvNaming) -> impl IntoIterator<Item = (String, &OsStr)> {
+        self.iter()
+            .filter_map(|(dir, val)| Some((naming.var_name(dir)?, val.as_os_str())))
+            .collect::<Vec<_>>()
     }
 }
 
@@ -513,146 +677,163 @@ impl InstallDirs {
 macro_rules! parse_env {
     () => {{
         let mut dirs = InstallDirs::defaults();
+        $crate::parse_env!(@read dirs);
+        dirs
+    }};
+    ($project:expr) => {{
+        let mut dirs = InstallDirs::with_project_name($project);
+        $crate::parse_env!(@read dirs);
+        dirs
+    }};
+    (@read $dirs:ident) => {
         if let Some(dir) = std::option_env!("prefix") {
-            dirs.prefix = dir.into();
+            $dirs.prefix = dir.into();
         }
 
         if let Some(dir) = std::option_env!("exec_prefix") {
-            dirs.exec_prefix = dir.into();
+            $dirs.exec_prefix = dir.into();
         }
 
         if let Some(dir) = std::option_env!("bindir") {
-            dirs.bindir = dir.into();
+            $dirs.bindir = dir.into();
         }
 
         if let Some(dir) = std::option_env!("sbindir") {
-            dirs.sbindir = dir.into();
+            $dirs.sbindir = dir.into();
         }
+
         if let Some(dir) = std::option_env!("libdir") {
-            dirs.libdir = dir.into();
+            $dirs.libdir = dir.into();
         }
 
         if let Some(dir) = std::option_env!("libexecdir") {
-            dirs.libexecdir = dir.into();
+            $dirs.libexecdir = dir.into();
         }
 
         if let Some(dir) = std::option_env!("includedir") {
-            dirs.includedir = dir.into();
+            $dirs.includedir = dir.into();
+        }
+
+        if let Some(dir) = std::option_env!("oldincludedir") {
+            $dirs.oldincludedir = dir.into();
         }
 
         if let Some(dir) = std::option_env!("datarootdir") {
-            dirs.datarootdir = dir.into();
+            $dirs.datarootdir = dir.into();
         }
 
         if let Some(dir) = std::option_env!("datadir") {
-            dirs.datadir = dir.into();
+            $dirs.datadir = dir.into();
         }
 
         if let Some(dir) = std::option_env!("mandir") {
-            dirs.mandir = dir.into();
+            $dirs.mandir = dir.into();
         }
 
-        if let Some(dir) = std::option_env!("docdir") {
-            dirs.docdir = dir.into();
+        if let Some(dir) = std::option_env!("man1dir") {
+            $dirs.man1dir = dir.into();
         }
 
-        if let Some(dir) = std::option_env!("infodir") {
-            dirs.infodir = dir.into();
+        if let Some(dir) = std::option_env!("man2dir") {
+            $dirs.man2dir = dir.into();
         }
 
-        if let Some(dir) = std::option_env!("localedir") {
-            dirs.localedir = dir.into();
+        if let Some(dir) = std::option_env!("man3dir") {
+            $dirs.man3dir = dir.into();
         }
 
-        if let Some(dir) = std::option_env!("sharedstatedir") {
-            dirs.sharedstatedir = dir.into();
+        if let Some(dir) = std::option_env!("man4dir") {
+            $dirs.man4dir = dir.into();
         }
 
-        if let Some(dir) = std::option_env!("localstatedir") {
-            dirs.localstatedir = dir.into();
+        if let Some(dir) = std::option_env!("man5dir") {
+            $dirs.man5dir = dir.into();
         }
 
-        if let Some(dir) = std::option_env!("runstatedir") {
-            dirs.runstatedir = dir.into();
+        if let Some(dir) = std::option_env!("man6dir") {
+            $dirs.man6dir = dir.into();
         }
 
-        if let Some(dir) = std::option_env!("sysconfdir") {
-            dirs.sysconfdir = dir.into();
+        if let Some(dir) = std::option_env!("man7dir") {
+            $dirs.man7dir = dir.into();
         }
 
-        dirs
-    }};
-    ($project:expr) => {{
-        let mut dirs = InstallDirs::with_project_name($project);
-        if let Some(dir) = std::option_env!("prefix") {
-            dirs.prefix = dir.into();
+        if let Some(dir) = std::option_env!("man8dir") {
+            $dirs.man8dir = dir.into();
         }
 
-        if let Some(dir) = std::option_env!("exec_prefix") {
-            dirs.exec_prefix = dir.into();
+        if let Some(dir) = std::option_env!("man9dir") {
+            $dirs.man9dir = dir.into();
         }
 
-        if let Some(dir) = std::option_env!("bindir") {
-            dirs.bindir = dir.into();
+        if let Some(dir) = std::option_env!("manext") {
+            $dirs.manext = dir.into();
         }
 
-        if let Some(dir) = std::option_env!("sbindir") {
-            dirs.sbindir = dir.into();
-        }
-        if let Some(dir) = std::option_env!("libdir") {
-            dirs.libdir = dir.into();
+        if let Some(dir) = std::option_env!("docdir") {
+            $dirs.docdir = dir.into();
         }
 
-        if let Some(dir) = std::option_env!("libexecdir") {
-            dirs.libexecdir = dir.into();
+        if let Some(dir) = std::option_env!("htmldir") {
+            $dirs.htmldir = dir.into();
         }
 
-        if let Some(dir) = std::option_env!("includedir") {
-            dirs.includedir = dir.into();
+        if let Some(dir) = std::option_env!("dvidir") {
+            $dirs.dvidir = dir.into();
         }
 
-        if let Some(dir) = std::option_env!("datarootdir") {
-            dirs.datarootdir = dir.into();
+        if let Some(dir) = std::option_env!("pdfdir") {
+            $dirs.pdfdir = dir.into();
         }
 
-        if let Some(dir) = std::option_env!("datadir") {
-            dirs.datadir = dir.into();
-        }
-
-        if let Some(dir) = std::option_env!("mandir") {
-            dirs.mandir = dir.into();
-        }
-
-        if let Some(dir) = std::option_env!("docdir") {
-            dirs.docdir = dir.into();
+        if let Some(dir) = std::option_env!("psdir") {
+            $dirs.psdir = dir.into();
         }
 
         if let Some(dir) = std::option_env!("infodir") {
-            dirs.infodir = dir.into();
+            $dirs.infodir = dir.into();
         }
 
         if let Some(dir) = std::option_env!("localedir") {
-            dirs.localedir = dir.into();
+            $dirs.localedir = dir.into();
+        }
+
+        if let Some(dir) = std::option_env!("lispdir") {
+            $dirs.lispdir = dir.into();
         }
 
         if let Some(dir) = std::option_env!("sharedstatedir") {
-            dirs.sharedstatedir = dir.into();
+            $dirs.sharedstatedir = dir.into();
         }
 
         if let Some(dir) = std::option_env!("localstatedir") {
-            dirs.localstatedir = dir.into();
+            $dirs.localstatedir = dir.into();
         }
 
         if let Some(dir) = std::option_env!("runstatedir") {
-            dirs.runstatedir = dir.into();
+            $dirs.runstatedir = dir.into();
         }
 
         if let Some(dir) = std::option_env!("sysconfdir") {
-            dirs.sysconfdir = dir.into();
+            $dirs.sysconfdir = dir.into();
         }
 
-        dirs
-    }};
+        if let Some(dir) = std::option_env!("pkgdatadir") {
+            $dirs.pkgdatadir = dir.into();
+        }
+
+        if let Some(dir) = std::option_env!("pkglibdir") {
+            $dirs.pkglibdir = dir.into();
+        }
+
+        if let Some(dir) = std::option_env!("pkgincludedir") {
+            $dirs.pkgincludedir = dir.into();
+        }
+
+        if let Some(dir) = std::option_env!("pkglibexecdir") {
+            $dirs.pkglibexecdir = dir.into();
+        }
+    };
 }
 
 pub fn from_env() -> InstallDirs {
@@ -660,3 +841,54 @@ pub fn from_env() -> InstallDirs {
     dirs.read_env();
     dirs
 }
+
+///
+/// Reads an instance of InstallDirs from `env`, using the default installation configuration for any variable that is not set
+pub fn from_env_source<E: EnvSource + ?Sized>(env: &E) -> InstallDirs {
+    let mut dirs = InstallDirs::defaults();
+    dirs.read_env_from(env);
+    dirs
+}
+
+///
+/// Returns `path` without its root (or prefix, on windows), so that it can be pushed onto another path
+fn without_root(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|c| !matches!(c, Component::Prefix(_) | Component::RootDir))
+        .collect()
+}
+
+///
+/// Returns the absolute `path` as seen from under `root`, such as `/sysroot/usr/lib` for `/usr/lib`
+fn under_root(root: &Path, path: &Path) -> PathBuf {
+    let mut out = root.to_path_buf();
+    out.extend(without_root(path).components());
+    out
+}
+
+///
+/// Removes `.` components from `path`, and removes the component before each `..` component.
+/// Returns `None` if a `..` component would go above the root, unless `parent_dirs` is [`ParentDirs::Clamp`].
+fn normalize(path: &Path, parent_dirs: ParentDirs) -> Option<PathBuf> {
+    let mut out = PathBuf::new();
+    let mut depth = 0usize;
+    for component in path.components() {
+        match component {
+            Component::Prefix(_) | Component::RootDir => out.push(component),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if depth > 0 {
+                    out.pop();
+                    depth -= 1;
+                } else if parent_dirs == ParentDirs::Reject {
+                    return None;
+                }
+            }
+            Component::Normal(name) => {
+                out.push(name);
+                depth += 1;
+            }
+        }
+    }
+    Some(out)
+}
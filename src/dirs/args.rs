@@ -0,0 +1,366 @@
+use std::{
+    error::Error,
+    ffi::{OsStr, OsString},
+    fmt::Display,
+};
+
+use super::{Dir, InstallDirs, Setting, SETTING_OPTIONS};
+
+///
+/// The reason a directory option could not be applied
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ArgErrorKind {
+    /// The option does not name any known directory
+    Unknown,
+    /// The option was the last argument, and no value was given with `=`
+    MissingValue,
+    /// The option is an abbreviation of more than one known option
+    Ambiguous(Vec<&'static str>),
+}
+
+///
+/// An error produced when a directory option cannot be parsed or applied
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ArgError {
+    key: String,
+    kind: ArgErrorKind,
+    suggestion: Option<&'static str>,
+}
+
+impl ArgError {
+    fn new(key: &str, kind: ArgErrorKind) -> Self {
+        Self {
+            key: key.to_string(),
+            kind,
+            suggestion: None,
+        }
+    }
+
+    ///
+    /// Creates an error for an option that is not known, suggesting the closest known option if there is one
+    pub(super) fn unknown(key: &str) -> Self {
+        Self {
+            suggestion: suggest(key),
+            ..Self::new(key, ArgErrorKind::Unknown)
+        }
+    }
+
+    ///
+    /// The option, as it was given on the command line
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    ///
+    /// The reason the option was rejected
+    pub fn kind(&self) -> &ArgErrorKind {
+        &self.kind
+    }
+
+    ///
+    /// The known option closest to [`ArgError::key`], if any is close enough to be a likely typo
+    pub fn suggestion(&self) -> Option<&'static str> {
+        self.suggestion
+    }
+}
+
+impl Display for ArgError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.kind {
+            ArgErrorKind::Unknown => f.write_fmt(format_args!("unknown option `{}`", self.key))?,
+            ArgErrorKind::MissingValue => {
+                f.write_fmt(format_args!("option `{}` requires a value", self.key))?
+            }
+            ArgErrorKind::Ambiguous(candidates) => {
+                f.write_fmt(format_args!(
+                    "option `{}` is ambiguous (could be ",
+                    self.key
+                ))?;
+                let mut sep = "";
+                for candidate in candidates {
+                    f.write_str(sep)?;
+                    sep = ", ";
+                    f.write_fmt(format_args!("`{}`", candidate))?;
+                }
+                f.write_str(")")?
+            }
+        }
+
+        if let Some(suggestion) = self.suggestion {
+            f.write_fmt(format_args!("; did you mean `{}`?", suggestion))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Error for ArgError {}
+
+///
+/// Computes the Levenshtein distance between `a` and `b`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut cur = vec![0; b.len() + 1];
+
+    for (i, ca) in a.chars().enumerate() {
+        cur[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let subst = prev[j] + if ca == cb { 0 } else { 1 };
+            cur[j + 1] = subst.min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut cur);
+    }
+
+    prev[b.len()]
+}
+
+///
+/// Finds the known option closest to `key`, if it is within a third of the length of `key`
+fn suggest(key: &str) -> Option<&'static str> {
+    let key = key.trim_start_matches('-');
+    let max = (key.len() / 3).max(1);
+    Dir::ALL
+        .iter()
+        .map(|dir| dir.option_name())
+        .map(|opt| (edit_distance(key, &opt[2..]), opt))
+        .filter(|&(dist, _)| dist <= max)
+        .min_by_key(|&(dist, _)| dist)
+        .map(|(_, opt)| opt)
+}
+
+///
+/// Splits `arg` into the option name (without leading dashes), whether it was written with two dashes,
+///  and the value following an `=`, if any.
+fn split_option(arg: &OsStr) -> Option<(&str, bool, Option<&OsStr>)> {
+    let bytes = arg.as_encoded_bytes();
+    let (bytes, long) = if let Some(rest) = bytes.strip_prefix(b"--") {
+        (rest, true)
+    } else if let Some(rest) = bytes.strip_prefix(b"-") {
+        (rest, false)
+    } else {
+        return None;
+    };
+
+    let (name, val) = match bytes.iter().position(|&b| b == b'=') {
+        Some(pos) => (&bytes[..pos], Some(&bytes[pos + 1..])),
+        None => (bytes, None),
+    };
+
+    let name = std::str::from_utf8(name).ok()?;
+    if name.is_empty() {
+        return None;
+    }
+    // SAFETY: `val` was split from an `OsStr` immediately after an ASCII `=`, and runs to the end of it
+    let val = val.map(|val| unsafe { OsStr::from_encoded_bytes_unchecked(val) });
+
+    Some((name, long, val))
+}
+
+///
+/// The options of an autoconf `configure` script which do not set a directory.
+/// These are never consumed, but are listed along with the directory options when an abbreviation is ambiguous.
+/// `--enable`, `--disable`, `--with` and `--without` stand for the options which name a feature or package, such as `--enable-foo`.
+const OTHER_OPTIONS: &[&str] = &[
+    "--build",
+    "--cache-file",
+    "--config-cache",
+    "--disable",
+    "--enable",
+    "--help",
+    "--host",
+    "--no-create",
+    "--no-recursion",
+    "--program-prefix",
+    "--program-suffix",
+    "--program-transform-name",
+    "--quiet",
+    "--silent",
+    "--site",
+    "--srcdir",
+    "--target",
+    "--verbose",
+    "--version",
+    "--with",
+    "--without",
+    "--x-includes",
+    "--x-libraries",
+];
+
+///
+/// The directory options defined by autoconf, each with the shortest abbreviation an autoconf `configure` script accepts for it.
+/// The other directories, such as `--man1dir` or `--pkglibdir`, are not known to `configure`, so must be spelled in full.
+const ABBREVIATIONS: &[(Dir, &str)] = &[
+    (Dir::Bindir, "--bi"),
+    (Dir::Datadir, "--datad"),
+    (Dir::Datarootdir, "--datar"),
+    (Dir::Docdir, "--do"),
+    (Dir::Dvidir, "--dv"),
+    (Dir::ExecPrefix, "--ex"),
+    (Dir::Htmldir, "--ht"),
+    (Dir::Includedir, "--inc"),
+    (Dir::Infodir, "--inf"),
+    (Dir::Libdir, "--libd"),
+    (Dir::Libexecdir, "--libe"),
+    (Dir::Localedir, "--locale"),
+    (Dir::Localstatedir, "--locals"),
+    (Dir::Mandir, "--m"),
+    (Dir::Oldincludedir, "--o"),
+    (Dir::Pdfdir, "--pd"),
+    (Dir::Prefix, "--p"),
+    (Dir::Psdir, "--ps"),
+    (Dir::Runstatedir, "--r"),
+    (Dir::Sbindir, "--sb"),
+    (Dir::Sharedstatedir, "--sha"),
+    (Dir::Sysconfdir, "--sy"),
+];
+
+///
+/// Finds the directory whose option is named by `name`, which may be an abbreviation accepted by `configure` if `long` is set.
+/// Underscores are accepted in place of dashes, so `--exec_prefix` matches `--exec-prefix`.
+///
+/// Returns `None` if `name` is not a directory option or an abbreviation of one,
+///  and an error if it abbreviates a directory option but is too short for `configure` to tell which option it is.
+fn lookup_option(name: &str, long: bool, key: &str) -> Result<Option<Dir>, ArgError> {
+    let name = name.replace('_', "-");
+    if let Some(&dir) = Dir::ALL.iter().find(|dir| dir.option_name()[2..] == *name) {
+        return Ok(Some(dir));
+    }
+
+    if !long || OTHER_OPTIONS.iter().any(|opt| opt[2..] == *name) {
+        return Ok(None);
+    }
+
+    let abbreviates = |opt: &str| opt[2..].starts_with(&*name);
+    if let Some(&(dir, _)) = ABBREVIATIONS
+        .iter()
+        .find(|&&(dir, min)| abbreviates(dir.option_name()) && name.starts_with(&min[2..]))
+    {
+        return Ok(Some(dir));
+    }
+
+    let mut candidates: Vec<&'static str> = ABBREVIATIONS
+        .iter()
+        .map(|&(dir, _)| dir.option_name())
+        .filter(|opt| abbreviates(opt))
+        .collect();
+    if candidates.is_empty() {
+        return Ok(None);
+    }
+    candidates.extend(OTHER_OPTIONS.iter().copied().filter(|opt| abbreviates(opt)));
+    candidates.sort_unstable();
+    Err(ArgError::new(key, ArgErrorKind::Ambiguous(candidates)))
+}
+
+impl InstallDirs {
+    ///
+    /// Parses the directory options from `args`, in any form accepted by an autoconf `configure` script,
+    ///  and returns the arguments that were not consumed, in order.
+    ///
+    /// Each option may be given as `--prefix=/x` or `--prefix /x`, and with two dashes may be abbreviated as far as `configure` allows,
+    ///  such as `--pref=/x` or `--p=/x` for `--prefix`, or `--man=/x` for `--mandir`. Options with a single dash, such as `-prefix /x`,
+    ///  must be spelled in full, as must the directory options which autoconf does not define (`--man1dir` to `--man9dir`, `--manext`,
+    ///  `--lispdir` and the `--pkg*dir` options).
+    /// An abbreviation too short for `configure` to accept, such as `--b` for `--bindir` or `--build`, is an error,
+    ///  and an abbreviation of an option which does not set a directory, such as `--he` for `--help`, is returned unchanged.
+    /// Arguments which do not name a directory option are returned unchanged, as is everything following a bare `--`.
+    /// Values are used as-is, even if they are not valid UTF-8.
+    ///
+    /// `--with-sysroot=DIR` sets [`InstallDirs::sysroot`]. As with `configure`, it must be spelled in full and given a value with `=`,
+    ///  and is otherwise returned unchanged.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use install_dirs::dirs::InstallDirs;
+    /// use std::ffi::OsString;
+    /// let mut dirs = InstallDirs::defaults();
+    /// let rest = dirs
+    ///     .parse_args(vec!["configure", "--pref=/opt", "--enable-foo", "--libdir", "lib64"])
+    ///     .unwrap();
+    /// assert_eq!(dirs.prefix.to_str(), Some("/opt"));
+    /// assert_eq!(dirs.libdir.to_str(), Some("lib64"));
+    /// assert_eq!(rest, vec![OsString::from("configure"), OsString::from("--enable-foo")]);
+    ///
+    /// dirs.parse_args(vec!["--p=/usr", "--man=/usr/man"]).unwrap();
+    /// assert_eq!(dirs.prefix.to_str(), Some("/usr"));
+    /// assert_eq!(dirs.mandir.to_str(), Some("/usr/man"));
+    ///
+    /// assert!(dirs.parse_args(vec!["--b", "/usr/bin"]).is_err());
+    /// assert_eq!(dirs.parse_args(vec!["--he"]).unwrap(), vec![OsString::from("--he")]);
+    /// assert_eq!(dirs.parse_args(vec!["--pkglib=lib"]).unwrap(), vec![OsString::from("--pkglib=lib")]);
+    /// ```
+    pub fn parse_args<I>(&mut self, args: I) -> Result<Vec<OsString>, ArgError>
+    where
+        I: IntoIterator,
+        I::Item: Into<OsString>,
+    {
+        parse_args_with(args, |setting, _, val| {
+            self.set_setting(setting, val.into())
+        })
+    }
+}
+
+///
+/// Parses the directory options and `--with-sysroot` from `args` as described by [`InstallDirs::parse_args`],
+///  calling `set` with each setting, the option as it was written, and the value.
+pub(super) fn parse_args_with<I, F>(args: I, mut set: F) -> Result<Vec<OsString>, ArgError>
+where
+    I: IntoIterator,
+    I::Item: Into<OsString>,
+    F: FnMut(Setting, &str, OsString),
+{
+    let mut args = args.into_iter().map(Into::into);
+    let mut rest = Vec::new();
+
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            rest.push(arg);
+            rest.extend(args);
+            break;
+        }
+
+        let setting = SETTING_OPTIONS.iter().find_map(|&(setting, option)| {
+            let val = arg
+                .as_encoded_bytes()
+                .strip_prefix(option.as_bytes())?
+                .strip_prefix(b"=")?;
+            Some((setting, option, val))
+        });
+        if let Some((setting, option, val)) = setting {
+            // SAFETY: `val` was split from an `OsStr` immediately after an ASCII `=`, and runs to the end of it
+            let val = unsafe { OsStr::from_encoded_bytes_unchecked(val) };
+            set(setting, option, val.to_os_string());
+            continue;
+        }
+
+        let (key, dir, val) = match split_option(&arg) {
+            Some((name, long, val)) => {
+                let key = format!("{}{}", if long { "--" } else { "-" }, name);
+                match lookup_option(name, long, &key)? {
+                    Some(dir) => (key, dir, val.map(OsStr::to_os_string)),
+                    None => {
+                        rest.push(arg);
+                        continue;
+                    }
+                }
+            }
+            None => {
+                rest.push(arg);
+                continue;
+            }
+        };
+
+        let val = match val {
+            Some(val) => val,
+            None => args
+                .next()
+                .ok_or_else(|| ArgError::new(&key, ArgErrorKind::MissingValue))?,
+        };
+
+        set(Setting::Dir(dir), &key, val);
+    }
+
+    Ok(rest)
+}
@@ -0,0 +1,335 @@
+use std::{
+    cmp::Ordering,
+    error::Error,
+    ffi::OsStr,
+    fmt::Display,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+};
+
+use super::{under_root, Dir, EnvNaming, InstallDirs};
+
+///
+/// The rule a directory broke during canonicalization
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CanonicalizationErrorKind {
+    /// `prefix` is not an absolute path, or the new `prefix` or `exec_prefix` given to [`CanonicalInstallDirs::rebase_with_exec_prefix`] is not
+    RelativePrefix,
+    /// The directory goes above the root directory, after being resolved to the contained path
+    EscapesRoot(PathBuf),
+    /// The directories depend on each other in a cycle, through references such as `${exec_prefix}` or their bases.
+    /// The cycle starts and ends with the same directory.
+    Cycle(Vec<Dir>),
+    /// The value contains a NUL byte, so cannot be passed to the operating system
+    ContainsNul,
+    /// The layout does not permit the absolute value of the directory, because it is not within the contained `prefix`
+    OutsidePrefix(PathBuf),
+    /// The layout resolved the directory to the contained path, which is not absolute
+    NotAbsolute(PathBuf),
+    /// The value contains the reference, which does not name a directory, so cannot be resolved.
+    /// This includes references to unknown names such as `${foo}`, to `${manext}`, which is not a directory,
+    ///  and references without braces such as `$prefix`, which are never expanded.
+    UnknownReference(String),
+}
+
+///
+/// A single problem found while canonicalizing a directory
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CanonicalizationIssue {
+    dir: Dir,
+    value: PathBuf,
+    kind: CanonicalizationErrorKind,
+}
+
+impl CanonicalizationIssue {
+    pub(super) fn new<P: Into<PathBuf>>(
+        dir: Dir,
+        value: P,
+        kind: CanonicalizationErrorKind,
+    ) -> Self {
+        Self {
+            dir,
+            value: value.into(),
+            kind,
+        }
+    }
+
+    ///
+    /// The directory with the problem
+    pub fn dir(&self) -> Dir {
+        self.dir
+    }
+
+    ///
+    /// The value of the directory, as it was before canonicalization
+    pub fn value(&self) -> &Path {
+        &self.value
+    }
+
+    ///
+    /// The rule the directory broke
+    pub fn kind(&self) -> &CanonicalizationErrorKind {
+        &self.kind
+    }
+}
+
+impl Display for CanonicalizationIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("{} {} ", self.dir, self.value.display()))?;
+        match &self.kind {
+            CanonicalizationErrorKind::RelativePrefix => f.write_str("is not an absolute path"),
+            CanonicalizationErrorKind::EscapesRoot(path) => f.write_fmt(format_args!(
+                "goes above the root directory (as {})",
+                path.display()
+            )),
+            CanonicalizationErrorKind::Cycle(cycle) => {
+                f.write_str("depends on itself: ")?;
+                for (i, dir) in cycle.iter().enumerate() {
+                    if i != 0 {
+                        f.write_str(" -> ")?;
+                    }
+                    f.write_fmt(format_args!("{}", dir))?;
+                }
+                Ok(())
+            }
+            CanonicalizationErrorKind::ContainsNul => f.write_str("contains a NUL byte"),
+            CanonicalizationErrorKind::OutsidePrefix(prefix) => {
+                f.write_fmt(format_args!("is not within prefix {}", prefix.display()))
+            }
+            CanonicalizationErrorKind::NotAbsolute(path) => f.write_fmt(format_args!(
+                "was resolved to {}, which is not an absolute path",
+                path.display()
+            )),
+            CanonicalizationErrorKind::UnknownReference(reference) => f.write_fmt(format_args!(
+                "contains `{}`, which is not a reference to a directory",
+                reference
+            )),
+        }
+    }
+}
+
+///
+/// An error produced when install directories cannot be canonicalized, listing every problem which was found
+///
+/// ## Example
+///
+/// ```
+/// use install_dirs::dirs::{CanonicalizationErrorKind, Dir, InstallDirs};
+/// let mut dirs = InstallDirs::defaults();
+/// dirs.prefix = "/usr".into();
+/// dirs.exec_prefix = "../..".into();
+/// dirs.libdir = "${pkglibdir}/..".into();
+/// dirs.pkglibdir = "${libdir}/foo".into();
+/// let err = dirs.canonicalize().unwrap_err();
+/// let issues = err.issues();
+/// assert_eq!(issues.len(), 2);
+/// assert_eq!(issues[0].dir(), Dir::ExecPrefix);
+/// assert_eq!(issues[0].value().to_str(), Some("../.."));
+/// assert_eq!(
+///     issues[1].kind(),
+///     &CanonicalizationErrorKind::Cycle(vec![Dir::Libdir, Dir::Pkglibdir, Dir::Libdir])
+/// );
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CanonicalizationError {
+    issues: Vec<CanonicalizationIssue>,
+}
+
+impl CanonicalizationError {
+    pub(super) fn new(issues: Vec<CanonicalizationIssue>) -> Self {
+        debug_assert!(!issues.is_empty());
+        Self { issues }
+    }
+
+    ///
+    /// Every problem which was found, in the order of [`Dir::ALL`]. This is never empty.
+    pub fn issues(&self) -> &[CanonicalizationIssue] {
+        &self.issues
+    }
+}
+
+impl Display for CanonicalizationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Failed to canonicalize Install Dirs (")?;
+        for (i, issue) in self.issues.iter().enumerate() {
+            if i != 0 {
+                f.write_str("; ")?;
+            }
+            issue.fmt(f)?;
+        }
+        f.write_str(")")
+    }
+}
+
+impl Error for CanonicalizationError {}
+
+///
+/// A set of install directories which has been resolved by [`InstallDirs::canonicalize`].
+///
+/// Every directory is an absolute path, except `manext`, which is not a directory.
+/// A `CanonicalInstallDirs` can only be obtained by canonicalizing an [`InstallDirs`], and cannot be modified,
+///  so APIs which need absolute paths can accept it to ensure canonicalization has taken place.
+///
+/// ## Example
+///
+/// ```
+/// use install_dirs::dirs::InstallDirs;
+/// let dirs = InstallDirs::defaults();
+/// let canonical = dirs.canonicalize().unwrap();
+/// assert!(canonical.bindir().is_absolute());
+/// ```
+///
+/// Two `CanonicalInstallDirs` compare equal if their canonical values are equal, regardless of their unexpanded values.
+#[derive(Clone, Debug)]
+pub struct CanonicalInstallDirs {
+    dirs: InstallDirs,
+    unexpanded: InstallDirs,
+}
+
+impl CanonicalInstallDirs {
+    ///
+    /// Wraps `dirs`, which must already be canonical, and which was canonicalized from `unexpanded`
+    pub(super) fn new(dirs: InstallDirs, unexpanded: InstallDirs) -> Self {
+        debug_assert!(dirs
+            .iter()
+            .all(|(dir, val)| !dir.is_path() || val.is_absolute()));
+        Self { dirs, unexpanded }
+    }
+
+    ///
+    /// Returns the canonical directories, and the directories they were canonicalized from
+    pub(super) fn into_parts(self) -> (InstallDirs, InstallDirs) {
+        (self.dirs, self.unexpanded)
+    }
+
+    ///
+    /// Returns the canonical value of `dir`
+    pub fn get(&self, dir: Dir) -> &Path {
+        self.dirs.get(dir)
+    }
+
+    ///
+    /// Returns the value of `dir` before canonicalization, with any references to other directories (such as `${exec_prefix}`) intact.
+    /// This is suitable for writing to a Makefile or a pkg-config file, where those references are expanded later.
+    pub fn unexpanded(&self, dir: Dir) -> &Path {
+        self.unexpanded.get(dir)
+    }
+
+    ///
+    /// Obtains an iterator over every directory and its canonical value, in the order of [`Dir::ALL`]
+    pub fn iter(&self) -> impl Iterator<Item = (Dir, &Path)> + '_ {
+        self.dirs.iter()
+    }
+
+    ///
+    /// Obtains an iterator suitable for passing to [`std::process::Command::envs`], see [`InstallDirs::as_env`]
+    pub fn as_env(&self) -> impl IntoIterator<Item = (&str, &OsStr)> {
+        self.dirs.as_env()
+    }
+
+    ///
+    /// Obtains an iterator like [`CanonicalInstallDirs::as_env`], with each variable named according to `naming`
+    pub fn as_env_named(&self, naming: &EnvNaming) -> impl IntoIterator<Item = (String, &OsStr)> {
+        self.dirs.as_env_named(naming)
+    }
+
+    ///
+    /// The root of the target system, see [`InstallDirs::sysroot`]
+    pub fn sysroot(&self) -> Option<&Path> {
+        self.dirs.sysroot.as_deref()
+    }
+
+    ///
+    /// Maps the absolute run-time `path` to where it is found on the build machine, under the [`CanonicalInstallDirs::sysroot`].
+    /// If no sysroot is set, or it is empty, `path` is returned unchanged.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use install_dirs::dirs::{Dir, InstallDirs};
+    /// use std::collections::HashMap;
+    /// let mut env = HashMap::new();
+    /// env.insert("PKG_CONFIG_SYSROOT_DIR", "/opt/sysroots/aarch64");
+    /// let mut dirs = InstallDirs::defaults();
+    /// dirs.prefix = "/usr".into();
+    /// dirs.read_env_from(&env);
+    /// let dirs = dirs.canonicalize().unwrap();
+    /// assert_eq!(dirs.includedir().to_str(), Some("/usr/include"));
+    /// assert_eq!(
+    ///     dirs.in_sysroot(dirs.includedir().join("zlib.h")).to_str(),
+    ///     Some("/opt/sysroots/aarch64/usr/include/zlib.h")
+    /// );
+    /// assert_eq!(dirs.sysroot_path(Dir::Includedir).to_str(), Some("/opt/sysroots/aarch64/usr/include"));
+    /// ```
+    pub fn in_sysroot<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        match self
+            .sysroot()
+            .filter(|sysroot| !sysroot.as_os_str().is_empty())
+        {
+            Some(sysroot) => under_root(sysroot, path.as_ref()),
+            None => path.as_ref().to_path_buf(),
+        }
+    }
+
+    ///
+    /// Returns where `dir` is found on the build machine, under the [`CanonicalInstallDirs::sysroot`], see [`CanonicalInstallDirs::in_sysroot`].
+    /// `manext`, which is not a directory, is returned unchanged.
+    pub fn sysroot_path(&self, dir: Dir) -> PathBuf {
+        if dir.is_path() {
+            self.in_sysroot(self.get(dir))
+        } else {
+            self.get(dir).to_path_buf()
+        }
+    }
+
+    ///
+    /// Returns a read-only view of the canonical directories as an [`InstallDirs`]
+    pub fn as_install_dirs(&self) -> &InstallDirs {
+        &self.dirs
+    }
+
+    ///
+    /// Returns the canonical directories as an [`InstallDirs`], which may then be modified
+    pub fn into_inner(self) -> InstallDirs {
+        self.dirs
+    }
+}
+
+impl AsRef<InstallDirs> for CanonicalInstallDirs {
+    fn as_ref(&self) -> &InstallDirs {
+        &self.dirs
+    }
+}
+
+impl From<CanonicalInstallDirs> for InstallDirs {
+    fn from(dirs: CanonicalInstallDirs) -> Self {
+        dirs.dirs
+    }
+}
+
+impl PartialEq for CanonicalInstallDirs {
+    fn eq(&self, other: &Self) -> bool {
+        self.dirs == other.dirs
+    }
+}
+
+impl Eq for CanonicalInstallDirs {}
+
+impl Hash for CanonicalInstallDirs {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.dirs.hash(state)
+    }
+}
+
+impl PartialOrd for CanonicalInstallDirs {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for CanonicalInstallDirs {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.dirs.cmp(&other.dirs)
+    }
+}
@@ -0,0 +1,209 @@
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use super::{CanonicalInstallDirs, InstallDirs, PartialInstallDirs, PhysicalDirs};
+
+macro_rules! define_dirs {
+    {
+        $($(#[$meta:meta])* $variant:ident => $field:ident, $option:literal, $base:expr;)*
+    } => {
+        ///
+        /// Names one of the directories in [`InstallDirs`]
+        #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        #[non_exhaustive]
+        pub enum Dir {
+            $($(#[$meta])* $variant),*
+        }
+
+        impl Dir {
+            ///
+            /// Every directory, in the order the fields are declared in [`InstallDirs`].
+            /// A directory always appears after its [`Dir::base`].
+            pub const ALL: &'static [Dir] = &[$(Dir::$variant),*];
+
+            ///
+            /// The name of the field in [`InstallDirs`], which is also the name of the make variable, such as `exec_prefix`
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(Dir::$variant => ::core::stringify!($field)),*
+                }
+            }
+
+            ///
+            /// The `configure` option which sets this directory, such as `--exec-prefix`
+            pub fn option_name(self) -> &'static str {
+                match self {
+                    $(Dir::$variant => $option),*
+                }
+            }
+
+            ///
+            /// The name of the environment variable read by [`InstallDirs::read_env`] and produced by [`InstallDirs::as_env`]
+            pub fn env_name(self) -> &'static str {
+                self.name()
+            }
+
+            ///
+            /// The directory a relative value of this directory is nominally resolved against,
+            ///  as given by the GNU Coding Standards. `None` for `prefix`, and for values which are not derived from another directory.
+            pub fn base(self) -> Option<Dir> {
+                match self {
+                    $(Dir::$variant => $base),*
+                }
+            }
+        }
+
+        impl InstallDirs {
+            ///
+            /// Returns the value of `dir`
+            pub fn get(&self, dir: Dir) -> &Path {
+                match dir {
+                    $(Dir::$variant => &self.$field),*
+                }
+            }
+
+            ///
+            /// Returns a mutable reference to the value of `dir`
+            pub fn get_mut(&mut self, dir: Dir) -> &mut PathBuf {
+                match dir {
+                    $(Dir::$variant => &mut self.$field),*
+                }
+            }
+        }
+
+        impl CanonicalInstallDirs {
+            $(
+                ///
+                #[doc = ::core::concat!("The canonical value of `", ::core::stringify!($field), "`")]
+                pub fn $field(&self) -> &Path {
+                    &self.as_install_dirs().$field
+                }
+            )*
+        }
+
+        impl PhysicalDirs {
+            $(
+                ///
+                #[doc = ::core::concat!("The physical path of `", ::core::stringify!($field), "`")]
+                pub fn $field(&self) -> &Path {
+                    self.get(Dir::$variant)
+                }
+            )*
+        }
+
+        impl PartialInstallDirs {
+            ///
+            /// Returns the value of `dir`, if it is set
+            pub fn get(&self, dir: Dir) -> Option<&Path> {
+                match dir {
+                    $(Dir::$variant => self.$field.as_deref()),*
+                }
+            }
+
+            ///
+            /// Returns a mutable reference to the value of `dir`
+            pub fn get_mut(&mut self, dir: Dir) -> &mut Option<PathBuf> {
+                match dir {
+                    $(Dir::$variant => &mut self.$field),*
+                }
+            }
+        }
+    };
+}
+
+define_dirs! {
+    Prefix => prefix, "--prefix", None;
+    ExecPrefix => exec_prefix, "--exec-prefix", Some(Dir::Prefix);
+    Bindir => bindir, "--bindir", Some(Dir::ExecPrefix);
+    Sbindir => sbindir, "--sbindir", Some(Dir::ExecPrefix);
+    Libdir => libdir, "--libdir", Some(Dir::ExecPrefix);
+    Libexecdir => libexecdir, "--libexecdir", Some(Dir::ExecPrefix);
+    Includedir => includedir, "--includedir", Some(Dir::Prefix);
+    Oldincludedir => oldincludedir, "--oldincludedir", None;
+    Datarootdir => datarootdir, "--datarootdir", Some(Dir::Prefix);
+    Datadir => datadir, "--datadir", Some(Dir::Datarootdir);
+    Mandir => mandir, "--mandir", Some(Dir::Datarootdir);
+    Man1dir => man1dir, "--man1dir", Some(Dir::Mandir);
+    Man2dir => man2dir, "--man2dir", Some(Dir::Mandir);
+    Man3dir => man3dir, "--man3dir", Some(Dir::Mandir);
+    Man4dir => man4dir, "--man4dir", Some(Dir::Mandir);
+    Man5dir => man5dir, "--man5dir", Some(Dir::Mandir);
+    Man6dir => man6dir, "--man6dir", Some(Dir::Mandir);
+    Man7dir => man7dir, "--man7dir", Some(Dir::Mandir);
+    Man8dir => man8dir, "--man8dir", Some(Dir::Mandir);
+    Man9dir => man9dir, "--man9dir", Some(Dir::Mandir);
+    /// The extension of installed man pages. This is not a directory, and is never resolved against another directory.
+    Manext => manext, "--manext", None;
+    Docdir => docdir, "--docdir", Some(Dir::Datarootdir);
+    Htmldir => htmldir, "--htmldir", Some(Dir::Docdir);
+    Dvidir => dvidir, "--dvidir", Some(Dir::Docdir);
+    Pdfdir => pdfdir, "--pdfdir", Some(Dir::Docdir);
+    Psdir => psdir, "--psdir", Some(Dir::Docdir);
+    Infodir => infodir, "--infodir", Some(Dir::Datarootdir);
+    Localedir => localedir, "--localedir", Some(Dir::Datarootdir);
+    Lispdir => lispdir, "--lispdir", Some(Dir::Datarootdir);
+    Localstatedir => localstatedir, "--localstatedir", Some(Dir::Prefix);
+    Runstatedir => runstatedir, "--runstatedir", Some(Dir::Localstatedir);
+    Sharedstatedir => sharedstatedir, "--sharedstatedir", Some(Dir::Prefix);
+    Sysconfdir => sysconfdir, "--sysconfdir", Some(Dir::Prefix);
+    Pkgdatadir => pkgdatadir, "--pkgdatadir", Some(Dir::Datadir);
+    Pkglibdir => pkglibdir, "--pkglibdir", Some(Dir::Libdir);
+    Pkgincludedir => pkgincludedir, "--pkgincludedir", Some(Dir::Includedir);
+    Pkglibexecdir => pkglibexecdir, "--pkglibexecdir", Some(Dir::Libexecdir);
+}
+
+impl Dir {
+    ///
+    /// Finds the directory with the given [`Dir::name`]
+    pub fn from_name(name: &str) -> Option<Dir> {
+        Self::ALL.iter().copied().find(|dir| dir.name() == name)
+    }
+
+    ///
+    /// Finds the directory set by the given `configure` option, spelled in full (see [`Dir::option_name`])
+    pub fn from_option(option: &str) -> Option<Dir> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|dir| dir.option_name() == option)
+    }
+
+    ///
+    /// Whether the value of this directory is a path. This is true for every directory except `manext`
+    pub fn is_path(self) -> bool {
+        self != Dir::Manext
+    }
+}
+
+impl Display for Dir {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl InstallDirs {
+    ///
+    /// Sets the value of `dir` to `val`
+    pub fn set<P: Into<PathBuf>>(&mut self, dir: Dir, val: P) {
+        *self.get_mut(dir) = val.into();
+    }
+
+    ///
+    /// Obtains an iterator over every directory and its value, in the order of [`Dir::ALL`]
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use install_dirs::dirs::{Dir, InstallDirs};
+    /// let dirs = InstallDirs::defaults();
+    /// for (dir, val) in dirs.iter() {
+    ///     assert_eq!(dirs.get(dir), val);
+    /// }
+    /// assert_eq!(dirs.iter().count(), Dir::ALL.len());
+    /// ```
+    pub fn iter(&self) -> impl Iterator<Item = (Dir, &Path)> + '_ {
+        Dir::ALL.iter().map(move |&dir| (dir, self.get(dir)))
+    }
+}
@@ -0,0 +1,330 @@
+use std::{
+    borrow::Borrow,
+    collections::{BTreeMap, HashMap},
+    ffi::{OsStr, OsString},
+    hash::{BuildHasher, Hash},
+    io,
+    path::Path,
+};
+
+use super::Dir;
+
+///
+/// A source of environment variables, which [`InstallDirs::read_env_from`](super::InstallDirs::read_env_from) reads directories from.
+pub trait EnvSource {
+    ///
+    /// Returns the value of the variable `name`, or `None` if it is not set
+    fn var_os(&self, name: &str) -> Option<OsString>;
+}
+
+impl<T: EnvSource + ?Sized> EnvSource for &T {
+    fn var_os(&self, name: &str) -> Option<OsString> {
+        T::var_os(self, name)
+    }
+}
+
+///
+/// The environment of the current process, as given by [`std::env::var_os`]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ProcessEnv;
+
+impl EnvSource for ProcessEnv {
+    fn var_os(&self, name: &str) -> Option<OsString> {
+        std::env::var_os(name)
+    }
+}
+
+impl<K, V, S> EnvSource for HashMap<K, V, S>
+where
+    K: Borrow<str> + Hash + Eq,
+    V: AsRef<OsStr>,
+    S: BuildHasher,
+{
+    fn var_os(&self, name: &str) -> Option<OsString> {
+        self.get(name).map(|val| val.as_ref().to_os_string())
+    }
+}
+
+impl<K, V> EnvSource for BTreeMap<K, V>
+where
+    K: Borrow<str> + Ord,
+    V: AsRef<OsStr>,
+{
+    fn var_os(&self, name: &str) -> Option<OsString> {
+        self.get(name).map(|val| val.as_ref().to_os_string())
+    }
+}
+
+///
+/// Adapts a closure which looks up a variable into an [`EnvSource`]
+///
+/// ## Example
+///
+/// ```
+/// use install_dirs::dirs::{FnEnv, InstallDirs};
+/// let mut dirs = InstallDirs::defaults();
+/// dirs.read_env_from(&FnEnv(|name: &str| match name {
+///     "libdir" => Some("lib64".into()),
+///     _ => None,
+/// }));
+/// assert_eq!(dirs.libdir.to_str(), Some("lib64"));
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct FnEnv<F>(pub F);
+
+impl<F: Fn(&str) -> Option<OsString>> EnvSource for FnEnv<F> {
+    fn var_os(&self, name: &str) -> Option<OsString> {
+        (self.0)(name)
+    }
+}
+
+///
+/// The convention used to name the environment variable for each directory
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Default)]
+#[non_exhaustive]
+pub enum EnvNaming {
+    /// The lowercase make variable names used by the GNU Coding Standards, such as `libdir` (see [`Dir::env_name`])
+    #[default]
+    Gnu,
+    /// The make variable names in uppercase, such as `LIBDIR`
+    Uppercase,
+    /// The uppercase names, prefixed by the contained string and an underscore, such as `MYAPP_LIBDIR`
+    Prefixed(String),
+    /// The cache variables used by CMake's `GNUInstallDirs` module, such as `CMAKE_INSTALL_LIBDIR`.
+    /// Only `prefix` and the directories defined by `GNUInstallDirs` have names in this convention.
+    CMake,
+}
+
+impl EnvNaming {
+    ///
+    /// Returns the naming convention that prefixes each uppercase name with `prefix`, followed by an underscore
+    pub fn prefixed<S: Into<String>>(prefix: S) -> Self {
+        EnvNaming::Prefixed(prefix.into())
+    }
+
+    ///
+    /// Returns the name of the variable for `dir`, or `None` if `dir` has no name in this convention
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use install_dirs::dirs::{Dir, EnvNaming};
+    /// assert_eq!(EnvNaming::Gnu.var_name(Dir::ExecPrefix).as_deref(), Some("exec_prefix"));
+    /// assert_eq!(EnvNaming::prefixed("MYAPP").var_name(Dir::Libdir).as_deref(), Some("MYAPP_LIBDIR"));
+    /// assert_eq!(EnvNaming::CMake.var_name(Dir::Bindir).as_deref(), Some("CMAKE_INSTALL_BINDIR"));
+    /// assert_eq!(EnvNaming::CMake.var_name(Dir::ExecPrefix), None);
+    /// ```
+    pub fn var_name(&self, dir: Dir) -> Option<String> {
+        match self {
+            EnvNaming::Gnu => Some(dir.env_name().to_string()),
+            EnvNaming::Uppercase => Some(dir.env_name().to_ascii_uppercase()),
+            EnvNaming::Prefixed(prefix) => Some(format!(
+                "{}_{}",
+                prefix,
+                dir.env_name().to_ascii_uppercase()
+            )),
+            EnvNaming::CMake => match dir {
+                Dir::Prefix => Some("CMAKE_INSTALL_PREFIX".to_string()),
+                Dir::Bindir
+                | Dir::Sbindir
+                | Dir::Libexecdir
+                | Dir::Sysconfdir
+                | Dir::Sharedstatedir
+                | Dir::Localstatedir
+                | Dir::Runstatedir
+                | Dir::Libdir
+                | Dir::Includedir
+                | Dir::Oldincludedir
+                | Dir::Datarootdir
+                | Dir::Datadir
+                | Dir::Infodir
+                | Dir::Localedir
+                | Dir::Mandir
+                | Dir::Docdir => Some(format!(
+                    "CMAKE_INSTALL_{}",
+                    dir.env_name().to_ascii_uppercase()
+                )),
+                _ => None,
+            },
+        }
+    }
+}
+
+///
+/// A captured set of environment variables, such as one parsed from a `.env` file,
+///  or read from the environment of another process.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EnvFile {
+    vars: HashMap<String, OsString>,
+}
+
+impl EnvFile {
+    ///
+    /// Parses the contents of a `.env` file.
+    ///
+    /// Each line is of the form `NAME=value`, optionally preceded by `export`. Blank lines and lines starting with `#` are ignored.
+    /// Values in single quotes are taken literally, and values in double quotes may contain the escapes `\n`, `\t`, `\"`, `\\` and `\$`.
+    /// A quote which is not closed runs to the end of the line.
+    /// Unquoted values end at the first ` #`, and have surrounding whitespace removed.
+    /// Lines which are not assignments are ignored, and later assignments override earlier ones.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use install_dirs::dirs::{EnvFile, EnvSource};
+    /// let env = EnvFile::parse(
+    ///     r##"
+    /// # A comment
+    /// export prefix=/opt/foo
+    /// libdir = lib64 # a trailing comment
+    /// docdir=share/doc#1
+    /// bindir="my bin\t\"x\" # kept"
+    /// sbindir='$HOME/sbin\n'
+    /// datadir="unterminated # kept
+    /// not an assignment
+    /// "##,
+    /// );
+    /// assert_eq!(env.var_os("prefix").unwrap(), "/opt/foo");
+    /// assert_eq!(env.var_os("libdir").unwrap(), "lib64");
+    /// assert_eq!(env.var_os("docdir").unwrap(), "share/doc#1");
+    /// assert_eq!(env.var_os("bindir").unwrap(), "my bin\t\"x\" # kept");
+    /// assert_eq!(env.var_os("sbindir").unwrap(), "$HOME/sbin\\n");
+    /// assert_eq!(env.var_os("datadir").unwrap(), "unterminated # kept");
+    /// assert_eq!(env.iter().count(), 6);
+    /// ```
+    pub fn parse(src: &str) -> Self {
+        let mut vars = HashMap::new();
+        for line in src.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let line = line
+                .strip_prefix("export")
+                .filter(|rest| rest.starts_with(char::is_whitespace))
+                .unwrap_or(line);
+
+            let (name, val) = match line.split_once('=') {
+                Some((name, val)) => (name.trim(), val.trim_start()),
+                None => continue,
+            };
+
+            if name.is_empty() || name.contains(char::is_whitespace) {
+                continue;
+            }
+
+            vars.insert(name.to_string(), OsString::from(parse_value(val)));
+        }
+        Self { vars }
+    }
+
+    ///
+    /// Reads and parses the `.env` file at `path`, see [`EnvFile::parse`]
+    pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        std::fs::read_to_string(path).map(|src| Self::parse(&src))
+    }
+
+    ///
+    /// Parses a NUL-separated list of `NAME=value` entries, in the format of `/proc/<pid>/environ`.
+    /// Values are preserved even if they are not valid UTF-8, except on platforms where an [`OsString`] must be unicode.
+    /// Entries whose names are not valid UTF-8 are ignored.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # #[cfg(unix)] {
+    /// use install_dirs::dirs::{EnvFile, EnvSource};
+    /// use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+    /// let env = EnvFile::parse_environ(b"prefix=/opt/f\xF6\xF6\0EMPTY=\0not an entry\0\xFF=ignored\0");
+    /// assert_eq!(env.var_os("prefix").as_deref(), Some(OsStr::from_bytes(b"/opt/f\xF6\xF6")));
+    /// assert_eq!(env.var_os("EMPTY").unwrap(), "");
+    /// assert_eq!(env.iter().count(), 2);
+    /// # }
+    /// ```
+    pub fn parse_environ(src: &[u8]) -> Self {
+        let mut vars = HashMap::new();
+        for entry in src.split(|&b| b == 0) {
+            let pos = match entry.iter().position(|&b| b == b'=') {
+                Some(pos) => pos,
+                None => continue,
+            };
+            let name = match std::str::from_utf8(&entry[..pos]) {
+                Ok(name) if !name.is_empty() => name,
+                _ => continue,
+            };
+            vars.insert(name.to_string(), bytes_to_os_string(&entry[pos + 1..]));
+        }
+        Self { vars }
+    }
+
+    ///
+    /// Reads a NUL-separated environment, such as `/proc/<pid>/environ`, see [`EnvFile::parse_environ`]
+    pub fn read_environ<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        std::fs::read(path).map(|src| Self::parse_environ(&src))
+    }
+
+    ///
+    /// Sets the variable `name` to `val`
+    pub fn set<K: Into<String>, V: Into<OsString>>(&mut self, name: K, val: V) {
+        self.vars.insert(name.into(), val.into());
+    }
+
+    ///
+    /// Obtains an iterator over every variable and its value, in an unspecified order
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &OsStr)> + '_ {
+        self.vars
+            .iter()
+            .map(|(name, val)| (name.as_str(), val.as_os_str()))
+    }
+}
+
+impl EnvSource for EnvFile {
+    fn var_os(&self, name: &str) -> Option<OsString> {
+        self.vars.get(name).cloned()
+    }
+}
+
+fn parse_value(val: &str) -> String {
+    if let Some(rest) = val.strip_prefix('\'') {
+        return rest.split('\'').next().unwrap_or("").to_string();
+    }
+
+    if let Some(rest) = val.strip_prefix('"') {
+        let mut out = String::new();
+        let mut chars = rest.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => break,
+                '\\' => match chars.next() {
+                    Some('n') => out.push('\n'),
+                    Some('t') => out.push('\t'),
+                    Some(c @ ('"' | '\\' | '$')) => out.push(c),
+                    Some(c) => {
+                        out.push('\\');
+                        out.push(c);
+                    }
+                    None => out.push('\\'),
+                },
+                c => out.push(c),
+            }
+        }
+        return out;
+    }
+
+    let val = match val.find(" #") {
+        Some(pos) => &val[..pos],
+        None => val,
+    };
+    val.trim_end().to_string()
+}
+
+#[cfg(unix)]
+fn bytes_to_os_string(bytes: &[u8]) -> OsString {
+    use std::os::unix::ffi::OsStringExt as _;
+    OsString::from_vec(bytes.to_vec())
+}
+
+#[cfg(not(unix))]
+fn bytes_to_os_string(bytes: &[u8]) -> OsString {
+    String::from_utf8_lossy(bytes).into_owned().into()
+}
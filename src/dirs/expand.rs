@@ -0,0 +1,337 @@
+use std::{
+    error::Error,
+    ffi::{OsStr, OsString},
+    fmt::Display,
+    path::PathBuf,
+};
+
+use super::{Dir, EnvSource, InstallDirs};
+
+///
+/// How [`InstallDirs::expand`] treats a variable or user which cannot be found
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub enum ExpandMode {
+    /// Leave the reference as it was written
+    #[default]
+    Lenient,
+    /// Return an error
+    Strict,
+}
+
+///
+/// The reason a directory could not be expanded
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ExpandErrorKind {
+    /// The environment variable is not set
+    UndefinedVariable(String),
+    /// The user named by `~user` does not exist, or their home directory could not be determined.
+    /// The name is empty for `~` on its own.
+    UnknownUser(String),
+}
+
+///
+/// An error produced by [`InstallDirs::expand`] in [`ExpandMode::Strict`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExpandError {
+    dir: Dir,
+    kind: ExpandErrorKind,
+}
+
+impl ExpandError {
+    ///
+    /// The directory whose value could not be expanded
+    pub fn dir(&self) -> Dir {
+        self.dir
+    }
+
+    ///
+    /// The reason the value could not be expanded
+    pub fn kind(&self) -> &ExpandErrorKind {
+        &self.kind
+    }
+}
+
+impl Display for ExpandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("cannot expand `{}`: ", self.dir))?;
+        match &self.kind {
+            ExpandErrorKind::UndefinedVariable(name) => {
+                f.write_fmt(format_args!("environment variable `{}` is not set", name))
+            }
+            ExpandErrorKind::UnknownUser(name) if name.is_empty() => {
+                f.write_str("the home directory is not known")
+            }
+            ExpandErrorKind::UnknownUser(name) => {
+                f.write_fmt(format_args!("unknown user `{}`", name))
+            }
+        }
+    }
+}
+
+impl Error for ExpandError {}
+
+impl InstallDirs {
+    ///
+    /// Expands a leading `~` or `~user`, and references to environment variables written as `$VAR` or `${VAR}`, in the value of each directory,
+    ///  looking up variables in `env`. This is not done by any other method, so values which come from somewhere other than a shell,
+    ///  such as a configuration file, can be expanded explicitly before they are canonicalized.
+    ///
+    /// `~` is the value of `HOME` (or `USERPROFILE` on windows), and `~user` is the home directory of `user` as listed in `/etc/passwd` on unix.
+    /// Users which only exist in other sources of the system user database, such as LDAP, sssd or systemd-homed, are not found,
+    ///  and `~user` is never expanded on other platforms.
+    /// References which name a directory, such as `${prefix}`, are left to [`InstallDirs::canonicalize`].
+    /// A reference which cannot be expanded is left as it was written in [`ExpandMode::Lenient`], and is an error in [`ExpandMode::Strict`].
+    /// On error, no directory is changed.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use install_dirs::dirs::{ExpandMode, InstallDirs};
+    /// use std::collections::HashMap;
+    /// let mut env = HashMap::new();
+    /// env.insert("HOME", "/home/me");
+    /// env.insert("ARCH", "x86_64");
+    /// let mut dirs = InstallDirs::defaults();
+    /// dirs.prefix = "~/.local".into();
+    /// dirs.libdir = "${exec_prefix}/lib/$ARCH".into();
+    /// dirs.expand(&env, ExpandMode::Strict).unwrap();
+    /// assert_eq!(dirs.prefix.to_str(), Some("/home/me/.local"));
+    /// assert_eq!(dirs.libdir.to_str(), Some("${exec_prefix}/lib/x86_64"));
+    ///
+    /// dirs.datadir = "$ARCH/share".into();
+    /// dirs.mandir = "$UNSET/man".into();
+    /// assert!(dirs.expand(&env, ExpandMode::Strict).is_err());
+    /// assert_eq!(dirs.datadir.to_str(), Some("$ARCH/share"));
+    /// ```
+    pub fn expand<E: EnvSource + ?Sized>(
+        &mut self,
+        env: &E,
+        mode: ExpandMode,
+    ) -> Result<(), ExpandError> {
+        let mut expanded = Vec::new();
+        for &dir in Dir::ALL {
+            if !dir.is_path() {
+                continue;
+            }
+            let (val, failed) = expand_value(self.get(dir).as_os_str(), env);
+            if let (Some(kind), ExpandMode::Strict) = (failed, mode) {
+                return Err(ExpandError { dir, kind });
+            }
+            expanded.extend(val.map(|val| (dir, val)));
+        }
+        for (dir, val) in expanded {
+            self.set(dir, val);
+        }
+        Ok(())
+    }
+}
+
+///
+/// Expands `val`, returning the expanded value (or `None` if nothing was expanded),
+///  and the first reference which could not be expanded, if any
+fn expand_value<E: EnvSource + ?Sized>(
+    val: &OsStr,
+    env: &E,
+) -> (Option<OsString>, Option<ExpandErrorKind>) {
+    let bytes = val.as_encoded_bytes();
+    let mut out = OsString::with_capacity(val.len());
+    let mut changed = false;
+    let mut failed = None;
+
+    let mut rest = bytes;
+    if let Some(tilde) = bytes.strip_prefix(b"~") {
+        let end = tilde
+            .iter()
+            .position(|&b| b == b'/' || (cfg!(windows) && b == b'\\'))
+            .unwrap_or(tilde.len());
+        let user = std::str::from_utf8(&tilde[..end]).unwrap_or("\u{fffd}");
+        match home_dir(user, env) {
+            Some(home) => {
+                out.push(home);
+                changed = true;
+            }
+            None => {
+                failed = Some(ExpandErrorKind::UnknownUser(user.to_string()));
+                out.push(piece(&bytes[..end + 1]));
+            }
+        }
+        rest = &tilde[end..];
+    }
+
+    while let Some(pos) = rest.iter().position(|&b| b == b'$') {
+        out.push(piece(&rest[..pos]));
+        let after = &rest[pos + 1..];
+        let (name, len) = if let Some(braced) = after.strip_prefix(b"{") {
+            match braced.iter().position(|&b| b == b'}') {
+                Some(end) => (&braced[..end], end + 2),
+                None => (&braced[..0], 0),
+            }
+        } else {
+            let end = after
+                .iter()
+                .position(|&b| !(b.is_ascii_alphanumeric() || b == b'_'))
+                .unwrap_or(after.len());
+            (&after[..end], end)
+        };
+
+        let name = std::str::from_utf8(name)
+            .ok()
+            .filter(|name| is_var_name(name) && Dir::from_name(name).is_none());
+        match name {
+            Some(name) => match env.var_os(name) {
+                Some(val) => {
+                    out.push(val);
+                    changed = true;
+                }
+                None => {
+                    failed.get_or_insert(ExpandErrorKind::UndefinedVariable(name.to_string()));
+                    out.push(piece(&rest[pos..pos + 1 + len]));
+                }
+            },
+            None => out.push(piece(&rest[pos..pos + 1 + len])),
+        }
+        rest = &rest[pos + 1 + len..];
+    }
+    out.push(piece(rest));
+
+    if !changed {
+        return (None, failed);
+    }
+    (Some(out), failed)
+}
+
+fn is_var_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+///
+/// Returns the home directory of `user`, or of the current user if `user` is empty
+fn home_dir<E: EnvSource + ?Sized>(user: &str, env: &E) -> Option<PathBuf> {
+    if user.is_empty() {
+        let home = env.var_os("HOME");
+        let home = if cfg!(windows) {
+            home.or_else(|| env.var_os("USERPROFILE"))
+        } else {
+            home
+        };
+        return home.filter(|home| !home.is_empty()).map(PathBuf::from);
+    }
+    user_home_dir(user)
+}
+
+///
+/// Returns the home directory of `user` from `/etc/passwd`. This does not consult NSS, so other sources of users are not searched.
+#[cfg(unix)]
+fn user_home_dir(user: &str) -> Option<PathBuf> {
+    use std::os::unix::ffi::OsStrExt as _;
+    let passwd = std::fs::read("/etc/passwd").ok()?;
+    passwd.split(|&b| b == b'\n').find_map(|line| {
+        let mut fields = line.split(|&b| b == b':');
+        if fields.next()? != user.as_bytes() {
+            return None;
+        }
+        let home = fields.nth(4)?;
+        Some(PathBuf::from(OsStr::from_bytes(home)))
+    })
+}
+
+#[cfg(not(unix))]
+fn user_home_dir(_: &str) -> Option<PathBuf> {
+    None
+}
+
+///
+/// Returns every directory referenced by `val`
+pub(super) fn dir_references(val: &OsStr) -> Vec<Dir> {
+    let mut dirs = Vec::new();
+    substitute(val, |reference| {
+        dirs.extend(referenced_dir(reference));
+        None
+    });
+    dirs
+}
+
+///
+/// Returns every reference in `val` which does not name a directory, as it was written
+pub(super) fn unknown_references(val: &OsStr) -> Vec<String> {
+    let mut unknown = Vec::new();
+    substitute(val, |reference| {
+        if referenced_dir(reference).is_none() {
+            unknown.push(reference.to_string_lossy().into_owned());
+        }
+        None
+    });
+    unknown
+}
+
+///
+/// Replaces each reference in `val` to a directory with the value of that directory in `dirs`.
+/// References to anything else are left as they are.
+pub(super) fn expand_dir_references(val: &OsStr, dirs: &InstallDirs) -> OsString {
+    substitute(val, |reference| {
+        referenced_dir(reference).map(|dir| dirs.get(dir).as_os_str().to_os_string())
+    })
+}
+
+///
+/// Returns the directory named by `reference`, if it is written as `${name}` or `$(name)` and `name` is a directory other than `manext`
+fn referenced_dir(reference: &OsStr) -> Option<Dir> {
+    let name = match reference.as_encoded_bytes() {
+        [b'$', b'{', name @ .., b'}'] | [b'$', b'(', name @ .., b')'] => name,
+        _ => return None,
+    };
+    Dir::from_name(std::str::from_utf8(name).ok()?).filter(|dir| dir.is_path())
+}
+
+///
+/// Replaces each reference `${name}`, `$(name)` or `$name` in `val` with `lookup(reference)`, where `reference` is the reference as it was written,
+///  or leaves it as it is if that returns `None`. A `$` which does not start a reference, such as one followed by `/`, is left as it is.
+pub(super) fn substitute<F: FnMut(&OsStr) -> Option<OsString>>(
+    val: &OsStr,
+    mut lookup: F,
+) -> OsString {
+    let mut out = OsString::with_capacity(val.len());
+    let mut rest = val.as_encoded_bytes();
+
+    while let Some(pos) = rest.iter().position(|&b| b == b'$') {
+        let end = match rest.get(pos + 1) {
+            Some(&open @ (b'{' | b'(')) => {
+                let close = if open == b'{' { b'}' } else { b')' };
+                match rest[pos + 2..].iter().position(|&b| b == close) {
+                    Some(len) => pos + 2 + len + 1,
+                    None => break,
+                }
+            }
+            Some(&b) if b.is_ascii_alphabetic() || b == b'_' => rest[pos + 1..]
+                .iter()
+                .position(|&b| !(b.is_ascii_alphanumeric() || b == b'_'))
+                .map_or(rest.len(), |len| pos + 1 + len),
+            _ => {
+                out.push(piece(&rest[..pos + 1]));
+                rest = &rest[pos + 1..];
+                continue;
+            }
+        };
+
+        let reference = piece(&rest[pos..end]);
+        out.push(piece(&rest[..pos]));
+        match lookup(reference) {
+            Some(replacement) => out.push(replacement),
+            None => out.push(reference),
+        }
+        rest = &rest[end..];
+    }
+    out.push(piece(rest));
+    out
+}
+
+///
+/// Converts `bytes`, which was split from the encoded bytes of an `OsStr` only at ASCII characters, back into an `OsStr`.
+/// Pieces must be joined with [`OsString::push`], which handles surrogates split across them on windows, rather than by their bytes.
+fn piece(bytes: &[u8]) -> &OsStr {
+    // SAFETY: every caller splits the encoded bytes of an `OsStr` immediately before or after an ASCII character
+    unsafe { OsStr::from_encoded_bytes_unchecked(bytes) }
+}
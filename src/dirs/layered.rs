@@ -0,0 +1,272 @@
+use std::{
+    collections::HashMap,
+    ffi::OsString,
+    fmt::Display,
+    io,
+    path::{Path, PathBuf},
+};
+
+use super::{
+    args::parse_args_with, env_settings, ArgError, Dir, EnvFile, EnvNaming, EnvSource, InstallDirs,
+    Setting,
+};
+
+///
+/// A source of configuration for an [`InstallDirsBuilder`], in increasing order of precedence
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Layer {
+    /// The base configuration the builder was created with
+    Default,
+    /// A site configuration file, such as `config.site`
+    SiteConfig,
+    /// Environment variables
+    Env,
+    /// Command line arguments
+    CommandLine,
+    /// Values set explicitly by the program
+    Override,
+}
+
+///
+/// Where the value of a directory came from
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Provenance {
+    /// The value from the base configuration
+    Default,
+    /// The variable `var` in the site configuration file at `path`
+    SiteConfig { path: PathBuf, var: String },
+    /// The environment variable `var`
+    Env { var: String },
+    /// The command line argument `option`, as it was written
+    Arg { option: String },
+    /// An explicit override set by the program
+    Override,
+}
+
+impl Provenance {
+    ///
+    /// The layer this value was set by
+    pub fn layer(&self) -> Layer {
+        match self {
+            Provenance::Default => Layer::Default,
+            Provenance::SiteConfig { .. } => Layer::SiteConfig,
+            Provenance::Env { .. } => Layer::Env,
+            Provenance::Arg { .. } => Layer::CommandLine,
+            Provenance::Override => Layer::Override,
+        }
+    }
+}
+
+impl Display for Provenance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Provenance::Default => f.write_str("default"),
+            Provenance::SiteConfig { path, var } => f.write_fmt(format_args!(
+                "from `{}` in site config `{}`",
+                var,
+                path.display()
+            )),
+            Provenance::Env { var } => f.write_fmt(format_args!("from env var `{}`", var)),
+            Provenance::Arg { option } => f.write_fmt(format_args!("from `{}` argument", option)),
+            Provenance::Override => f.write_str("from explicit override"),
+        }
+    }
+}
+
+///
+/// Builds an [`InstallDirs`] from several sources, recording where each value came from.
+///
+/// Values are merged according to their [`Layer`], regardless of the order the sources are added in:
+///  defaults, then site configuration files, then the environment, then command line arguments, then explicit overrides.
+/// Within a layer, sources added later take precedence.
+///
+/// [`InstallDirs::destdir`] and [`InstallDirs::sysroot`] are merged in the same way, from `DESTDIR` and `PKG_CONFIG_SYSROOT_DIR`
+///  in the environment, and from `--with-sysroot` on the command line.
+///
+/// ## Example
+///
+/// ```
+/// use install_dirs::dirs::{Dir, EnvNaming, InstallDirs, InstallDirsBuilder};
+/// use std::collections::HashMap;
+/// let mut env = HashMap::new();
+/// env.insert("libdir", "lib64");
+/// env.insert("bindir", "bin64");
+/// let mut builder = InstallDirsBuilder::new(InstallDirs::defaults());
+/// builder.args(vec!["--libdir=lib32"]).unwrap();
+/// builder.env(&env, &EnvNaming::Gnu);
+/// let layered = builder.build();
+/// assert_eq!(layered.dirs().libdir.to_str(), Some("lib32"));
+/// assert_eq!(layered.provenance(Dir::Libdir).to_string(), "from `--libdir` argument");
+/// assert_eq!(layered.provenance(Dir::Bindir).to_string(), "from env var `bindir`");
+/// assert_eq!(layered.provenance(Dir::Sbindir).to_string(), "default");
+///
+/// let mut env = HashMap::new();
+/// env.insert("PKG_CONFIG_SYSROOT_DIR", "/opt/sysroots/aarch64");
+/// builder.args(vec!["--with-sysroot=/srv/sysroot"]).unwrap();
+/// builder.env(&env, &EnvNaming::Gnu);
+/// let layered = builder.build();
+/// assert_eq!(layered.dirs().sysroot.as_ref().unwrap().to_str(), Some("/srv/sysroot"));
+/// assert_eq!(layered.sysroot_provenance().unwrap().to_string(), "from `--with-sysroot` argument");
+/// assert_eq!(layered.destdir_provenance(), None);
+/// ```
+#[derive(Clone, Debug)]
+pub struct InstallDirsBuilder {
+    base: InstallDirs,
+    values: Vec<(Setting, PathBuf, Provenance)>,
+}
+
+impl InstallDirsBuilder {
+    ///
+    /// Creates a builder whose default layer is `base`
+    pub fn new(base: InstallDirs) -> Self {
+        Self {
+            base,
+            values: Vec::new(),
+        }
+    }
+
+    ///
+    /// Reads the site configuration file at `path`, which contains `name=value` assignments using the names from [`Dir::env_name`].
+    /// See [`EnvFile::parse`] for the accepted syntax.
+    pub fn site_config<P: AsRef<Path>>(&mut self, path: P) -> io::Result<&mut Self> {
+        let file = EnvFile::read(path.as_ref())?;
+        Ok(self.site_config_from(path.as_ref(), &file))
+    }
+
+    ///
+    /// Adds the site configuration file at `path`, whose contents have already been read into `vars`
+    pub fn site_config_from<P: Into<PathBuf>, E: EnvSource + ?Sized>(
+        &mut self,
+        path: P,
+        vars: &E,
+    ) -> &mut Self {
+        let path = path.into();
+        for &dir in Dir::ALL {
+            if let Some(val) = vars.var_os(dir.env_name()) {
+                self.values.push((
+                    Setting::Dir(dir),
+                    val.into(),
+                    Provenance::SiteConfig {
+                        path: path.clone(),
+                        var: dir.env_name().to_string(),
+                    },
+                ));
+            }
+        }
+        self
+    }
+
+    ///
+    /// Adds the variables in `env`, named according to `naming`, and `DESTDIR` and `PKG_CONFIG_SYSROOT_DIR`,
+    ///  see [`InstallDirs::read_env_named`]
+    pub fn env<E: EnvSource + ?Sized>(&mut self, env: &E, naming: &EnvNaming) -> &mut Self {
+        for (setting, var, val) in env_settings(env, naming) {
+            self.values
+                .push((setting, val.into(), Provenance::Env { var }));
+        }
+        self
+    }
+
+    ///
+    /// Adds the directory options in `args`, and returns the arguments which were not consumed.
+    /// See [`InstallDirs::parse_args`] for the accepted forms.
+    pub fn args<I>(&mut self, args: I) -> Result<Vec<OsString>, ArgError>
+    where
+        I: IntoIterator,
+        I::Item: Into<OsString>,
+    {
+        let values = &mut self.values;
+        parse_args_with(args, |setting, option, val| {
+            values.push((
+                setting,
+                val.into(),
+                Provenance::Arg {
+                    option: option.to_string(),
+                },
+            ))
+        })
+    }
+
+    ///
+    /// Overrides `dir` with `val`, which takes precedence over every other source
+    pub fn set<P: Into<PathBuf>>(&mut self, dir: Dir, val: P) -> &mut Self {
+        self.values
+            .push((Setting::Dir(dir), val.into(), Provenance::Override));
+        self
+    }
+
+    ///
+    /// Merges every source, and returns the resulting directories along with where each came from
+    pub fn build(&self) -> LayeredDirs {
+        let mut dirs = self.base.clone();
+        let mut provenance: HashMap<Setting, Provenance> = Dir::ALL
+            .iter()
+            .map(|&dir| (Setting::Dir(dir), Provenance::Default))
+            .collect();
+        if dirs.destdir.is_some() {
+            provenance.insert(Setting::Destdir, Provenance::Default);
+        }
+        if dirs.sysroot.is_some() {
+            provenance.insert(Setting::Sysroot, Provenance::Default);
+        }
+
+        let mut values: Vec<_> = self.values.iter().collect();
+        values.sort_by_key(|(_, _, provenance)| provenance.layer());
+
+        for (setting, val, source) in values {
+            dirs.set_setting(*setting, val.clone());
+            provenance.insert(*setting, source.clone());
+        }
+
+        LayeredDirs { dirs, provenance }
+    }
+}
+
+///
+/// An [`InstallDirs`] produced by an [`InstallDirsBuilder`], along with the [`Provenance`] of each directory
+#[derive(Clone, Debug)]
+pub struct LayeredDirs {
+    dirs: InstallDirs,
+    provenance: HashMap<Setting, Provenance>,
+}
+
+impl LayeredDirs {
+    ///
+    /// The merged directories
+    pub fn dirs(&self) -> &InstallDirs {
+        &self.dirs
+    }
+
+    ///
+    /// Discards the provenance, and returns the merged directories
+    pub fn into_dirs(self) -> InstallDirs {
+        self.dirs
+    }
+
+    ///
+    /// Where the value of `dir` came from
+    pub fn provenance(&self, dir: Dir) -> &Provenance {
+        &self.provenance[&Setting::Dir(dir)]
+    }
+
+    ///
+    /// Where [`InstallDirs::destdir`] came from, or `None` if it is not set
+    pub fn destdir_provenance(&self) -> Option<&Provenance> {
+        self.provenance.get(&Setting::Destdir)
+    }
+
+    ///
+    /// Where [`InstallDirs::sysroot`] came from, or `None` if it is not set
+    pub fn sysroot_provenance(&self) -> Option<&Provenance> {
+        self.provenance.get(&Setting::Sysroot)
+    }
+
+    ///
+    /// Obtains an iterator over every directory, its value, and where that value came from, in the order of [`Dir::ALL`]
+    pub fn iter(&self) -> impl Iterator<Item = (Dir, &Path, &Provenance)> + '_ {
+        self.dirs
+            .iter()
+            .map(move |(dir, val)| (dir, val, self.provenance(dir)))
+    }
+}
@@ -0,0 +1,570 @@
+use std::path::{Path, PathBuf};
+
+use super::{expand, without_root, Dir, InstallDirs};
+
+///
+/// The rules [`InstallDirs::canonicalize_with`] uses to resolve relative directories into absolute paths,
+///  along with the default directories those rules are meant to be used with.
+///
+/// The provided methods resolve each directory against its [`Dir::base`] (or `prefix` if it has none), as given by the GNU Coding Standards,
+///  so an implementation need only override the rules that differ. [`Layout`] provides the rules used by common build systems,
+///  and [`Layout::Default`] is the policy used by [`InstallDirs::canonicalize`].
+///
+/// ## Example
+///
+/// ```
+/// use install_dirs::dirs::{Dir, InstallDirs, LayoutContext, LayoutPolicy};
+/// use std::path::PathBuf;
+///
+/// // Places configuration files in `/etc/<prefix>`, and everything else under `prefix`
+/// struct EtcUnderRoot;
+///
+/// impl LayoutPolicy for EtcUnderRoot {
+///     fn resolve(&self, dir: Dir, cx: &LayoutContext) -> PathBuf {
+///         match dir {
+///             Dir::Sysconfdir => PathBuf::from("/etc").join(cx.prefix().strip_prefix("/").unwrap()),
+///             _ => cx.resolved(self.base(dir).unwrap_or(Dir::Prefix)).join(cx.raw(dir)),
+///         }
+///     }
+/// }
+///
+/// let mut dirs = InstallDirs::defaults();
+/// dirs.prefix = "/srv/app".into();
+/// let dirs = dirs.canonicalize_with(&EtcUnderRoot).unwrap();
+/// assert_eq!(dirs.sysconfdir().to_str(), Some("/etc/srv/app"));
+/// assert_eq!(dirs.libdir().to_str(), Some("/srv/app/lib"));
+/// ```
+pub trait LayoutPolicy {
+    ///
+    /// The default directories used with this policy, see [`InstallDirs::defaults_for`]
+    fn defaults(&self) -> InstallDirs {
+        InstallDirs::defaults()
+    }
+
+    ///
+    /// The directory which must be resolved before a relative value of `dir` can be resolved, or `None` if `dir` depends only on `prefix`.
+    /// A cycle between bases, or between bases and the directories referenced by values (such as `${exec_prefix}`), is an error.
+    fn base(&self, dir: Dir) -> Option<Dir> {
+        dir.base()
+    }
+
+    ///
+    /// Resolves the relative value of `dir`, `cx.raw(dir)`, into an absolute path.
+    /// Every directory this policy gives as a [`LayoutPolicy::base`] of `dir` (transitively) has already been resolved,
+    ///  and references to other directories in `cx.raw(dir)` have already been expanded.
+    ///
+    /// If the result is not absolute, canonicalization fails with [`CanonicalizationErrorKind::NotAbsolute`](super::CanonicalizationErrorKind::NotAbsolute).
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use install_dirs::dirs::{CanonicalizationErrorKind, Dir, InstallDirs, LayoutContext, LayoutPolicy};
+    /// use std::path::PathBuf;
+    ///
+    /// struct Unresolved;
+    ///
+    /// impl LayoutPolicy for Unresolved {
+    ///     fn resolve(&self, dir: Dir, cx: &LayoutContext) -> PathBuf {
+    ///         cx.raw(dir).to_path_buf()
+    ///     }
+    /// }
+    ///
+    /// let err = InstallDirs::defaults().canonicalize_with(&Unresolved).unwrap_err();
+    /// assert_eq!(err.issues()[0].dir(), Dir::ExecPrefix);
+    /// assert_eq!(err.issues()[0].kind(), &CanonicalizationErrorKind::NotAbsolute(PathBuf::new()));
+    /// ```
+    fn resolve(&self, dir: Dir, cx: &LayoutContext) -> PathBuf {
+        join(
+            cx.resolved(self.base(dir).unwrap_or(Dir::Prefix)),
+            cx.raw(dir),
+        )
+    }
+
+    ///
+    /// Whether `dir` may have the absolute value `val`, given the absolute `prefix`
+    fn permits(&self, dir: Dir, val: &Path, prefix: &Path) -> bool {
+        let _ = (dir, val, prefix);
+        true
+    }
+}
+
+impl<P: LayoutPolicy + ?Sized> LayoutPolicy for &P {
+    fn defaults(&self) -> InstallDirs {
+        P::defaults(self)
+    }
+
+    fn base(&self, dir: Dir) -> Option<Dir> {
+        P::base(self, dir)
+    }
+
+    fn resolve(&self, dir: Dir, cx: &LayoutContext) -> PathBuf {
+        P::resolve(self, dir, cx)
+    }
+
+    fn permits(&self, dir: Dir, val: &Path, prefix: &Path) -> bool {
+        P::permits(self, dir, val, prefix)
+    }
+}
+
+///
+/// The directories available to [`LayoutPolicy::resolve`]
+#[derive(Copy, Clone, Debug)]
+pub struct LayoutContext<'a> {
+    raw: &'a InstallDirs,
+    resolved: &'a InstallDirs,
+}
+
+impl<'a> LayoutContext<'a> {
+    pub(super) fn new(raw: &'a InstallDirs, resolved: &'a InstallDirs) -> Self {
+        Self { raw, resolved }
+    }
+
+    ///
+    /// The value of `dir` before it was resolved, with any references to other directories expanded
+    pub fn raw(&self, dir: Dir) -> &'a Path {
+        self.raw.get(dir)
+    }
+
+    ///
+    /// The value of `dir` once it has been resolved.
+    /// If `dir` has not been resolved yet, this is the same as [`LayoutContext::raw`].
+    pub fn resolved(&self, dir: Dir) -> &'a Path {
+        self.resolved.get(dir)
+    }
+
+    ///
+    /// The absolute `prefix`
+    pub fn prefix(&self) -> &'a Path {
+        &self.resolved.prefix
+    }
+}
+
+///
+/// Returns every path directory in an order in which each directory comes after `prefix`, the directories its value in `dirs` references,
+///  and, if its value is relative, the base `policy` gives it.
+/// Also returns each cycle of directories which depend on each other, starting and ending with the same directory.
+/// A dependency which would complete a cycle is ignored when ordering the directories.
+pub(super) fn resolution_order<P: LayoutPolicy + ?Sized>(
+    policy: &P,
+    dirs: &InstallDirs,
+) -> (Vec<Dir>, Vec<Vec<Dir>>) {
+    struct Visitor<'a, P: ?Sized> {
+        policy: &'a P,
+        dirs: &'a InstallDirs,
+        visiting: Vec<Dir>,
+        order: Vec<Dir>,
+        cycles: Vec<Vec<Dir>>,
+    }
+
+    impl<P: LayoutPolicy + ?Sized> Visitor<'_, P> {
+        fn visit(&mut self, dir: Dir) {
+            if self.order.contains(&dir) {
+                return;
+            }
+            if let Some(pos) = self.visiting.iter().position(|&d| d == dir) {
+                let mut cycle = self.visiting[pos..].to_vec();
+                cycle.push(dir);
+                self.cycles.push(cycle);
+                return;
+            }
+
+            let val = self.dirs.get(dir);
+            let mut deps = expand::dir_references(val.as_os_str());
+            if dir != Dir::Prefix {
+                deps.push(Dir::Prefix);
+            }
+            if val.is_relative() && !val.as_os_str().as_encoded_bytes().starts_with(b"$") {
+                deps.extend(self.policy.base(dir));
+            }
+
+            self.visiting.push(dir);
+            for dep in deps {
+                if dep.is_path() {
+                    self.visit(dep);
+                }
+            }
+            self.visiting.pop();
+            self.order.push(dir);
+        }
+    }
+
+    let mut visitor = Visitor {
+        policy,
+        dirs,
+        visiting: Vec::new(),
+        order: Vec::with_capacity(Dir::ALL.len()),
+        cycles: Vec::new(),
+    };
+    for &dir in Dir::ALL {
+        if dir.is_path() {
+            visitor.visit(dir);
+        }
+    }
+    (visitor.order, visitor.cycles)
+}
+
+///
+/// The rules used by common build systems to resolve relative directories into absolute paths, see [`LayoutPolicy`].
+///
+/// Every layout leaves absolute directories unchanged.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Default)]
+#[non_exhaustive]
+pub enum Layout {
+    ///
+    /// The rules used by [`InstallDirs::canonicalize`]. Each directory is resolved against its [`Dir::base`], except that:
+    /// * `includedir` is resolved against `exec_prefix`,
+    /// * `datarootdir` and `sharedstatedir` are resolved against `/usr` if `prefix` is `/`, and against `prefix` otherwise,
+    /// * `sysconfdir`, `localstatedir` and `runstatedir` are resolved against `/` if `prefix` starts with `/usr`,
+    /// * `sysconfdir`, `localstatedir` and `runstatedir` are resolved against `/` with `prefix` appended if `prefix` starts with `/opt`,
+    ///   as the FHS requires of packages in `/opt`, so with a `prefix` of `/opt/foo` they are `/etc/opt/foo`, `/var/opt/foo` and `/run/opt/foo`,
+    /// * otherwise, `sysconfdir`, `localstatedir` and `runstatedir` are resolved against `prefix`.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use install_dirs::dirs::InstallDirs;
+    /// let mut dirs = InstallDirs::defaults();
+    /// dirs.prefix = "/opt/foo".into();
+    /// let dirs = dirs.canonicalize().unwrap();
+    /// assert_eq!(dirs.bindir().to_str(), Some("/opt/foo/bin"));
+    /// assert_eq!(dirs.sysconfdir().to_str(), Some("/etc/opt/foo"));
+    /// assert_eq!(dirs.localstatedir().to_str(), Some("/var/opt/foo"));
+    /// assert_eq!(dirs.runstatedir().to_str(), Some("/run/opt/foo"));
+    /// ```
+    #[default]
+    Default,
+    ///
+    /// The rules given by the GNU Coding Standards, producing the same paths as an autoconf `configure` script.
+    /// Each directory is resolved against its [`Dir::base`] (or `prefix` if it has none), with no special cases for any `prefix`.
+    /// An empty directory is exactly its base, as with `datadir='${datarootdir}'`.
+    ///
+    /// This deliberately departs from `configure` for relative values. `configure` rejects any directory which is neither absolute
+    ///  nor starts with a reference such as `${exec_prefix}` ("expected an absolute directory name for --libdir"),
+    ///  but the defaults in [`InstallDirs`] are relative, so a relative value is instead resolved against its base
+    ///  as if the reference to the base had been written, and `libdir = "lib64"` is resolved as `${exec_prefix}/lib64`.
+    /// Validate the values given on the command line before canonicalizing them to reject relative values as `configure` does.
+    Gnu,
+    ///
+    /// The rules used by CMake's `GNUInstallDirs` module, producing the same paths as the `CMAKE_INSTALL_FULL_<dir>` variables.
+    ///
+    /// A relative directory is taken as relative to `prefix`, following its [`Dir::base`] while the base is also relative, except that:
+    /// * if `prefix` is `/`, `sysconfdir`, `localstatedir` and `runstatedir` are placed under `/`, and every other directory under `/usr`,
+    /// * if `prefix` is `/usr`, `sysconfdir`, `localstatedir` and `runstatedir` are placed under `/`,
+    /// * if `prefix` is `/opt/<pkg>`, `sysconfdir`, `localstatedir` and `runstatedir` are placed under `/etc/opt/<pkg>`, `/var/opt/<pkg>`
+    ///   and `/var/run/opt/<pkg>` respectively (or the equivalent for the values of those directories).
+    ///
+    /// If `libdir` is the default `lib`, it is first replaced according to `libdir`, see [`LibdirConvention`].
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use install_dirs::dirs::{InstallDirs, Layout, LibdirConvention};
+    /// let mut dirs = InstallDirs::defaults();
+    /// dirs.prefix = "/opt/foo".into();
+    /// let layout = Layout::CMake { libdir: LibdirConvention::Lib64 };
+    /// let dirs = dirs.canonicalize_with(&layout).unwrap();
+    /// assert_eq!(dirs.libdir().to_str(), Some("/opt/foo/lib64"));
+    /// assert_eq!(dirs.sysconfdir().to_str(), Some("/etc/opt/foo"));
+    /// assert_eq!(dirs.runstatedir().to_str(), Some("/var/run/opt/foo"));
+    /// ```
+    CMake {
+        /// The convention for the default `libdir`
+        libdir: LibdirConvention,
+    },
+    ///
+    /// The rules used by Meson, producing the same paths as its builtin directory options.
+    /// This should be used with the defaults given by [`InstallDirs::defaults_for`], which are relative to `prefix` as in Meson.
+    ///
+    /// Each of the directories Meson defines (`bindir`, `sbindir`, `libdir`, `libexecdir`, `includedir`, `datadir`, `mandir`, `infodir`,
+    ///  `localedir`, `localstatedir`, `sharedstatedir` and `sysconfdir`) is resolved against `prefix`, except that:
+    /// * if `prefix` is `/usr`, the default `sysconfdir`, `localstatedir` and `sharedstatedir` are `/etc`, `/var` and `/var/lib`,
+    /// * if `prefix` is `/usr/local`, the default `localstatedir` and `sharedstatedir` are `/var/local` and `/var/local/lib`.
+    ///
+    /// Every other directory is resolved against its [`Dir::base`].
+    ///
+    /// As in Meson, it is an error for any of the directories Meson defines, other than `sysconfdir`, `localstatedir` and `sharedstatedir`,
+    ///  to be an absolute path outside of `prefix`.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use install_dirs::dirs::{InstallDirs, Layout, LibdirConvention};
+    /// let layout = Layout::Meson { libdir: LibdirConvention::Multiarch("x86_64-linux-gnu".into()) };
+    /// let mut dirs = InstallDirs::defaults_for(&layout);
+    /// dirs.prefix = "/usr".into();
+    /// let canonical = dirs.clone().canonicalize_with(&layout).unwrap();
+    /// assert_eq!(canonical.libdir().to_str(), Some("/usr/lib/x86_64-linux-gnu"));
+    /// assert_eq!(canonical.sharedstatedir().to_str(), Some("/var/lib"));
+    /// dirs.bindir = "/bin".into();
+    /// assert!(dirs.canonicalize_with(&layout).is_err());
+    /// ```
+    Meson {
+        /// The convention for the default `libdir`
+        libdir: LibdirConvention,
+    },
+}
+
+///
+/// The system convention for the name of the directory which contains object code libraries
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Default)]
+#[non_exhaustive]
+pub enum LibdirConvention {
+    /// Libraries are installed to `lib`
+    #[default]
+    Lib,
+    /// Libraries are installed to `lib64`, as on 64-bit Fedora and SUSE
+    Lib64,
+    /// Libraries are installed to `lib/<tuple>`, as on Debian and its derivatives.
+    /// The contained string is the multiarch tuple, such as `x86_64-linux-gnu`.
+    ///
+    /// [`Layout::CMake`] only uses this convention when `prefix` is `/usr`, and uses `lib` otherwise.
+    Multiarch(String),
+}
+
+impl LibdirConvention {
+    ///
+    /// Detects the convention used by the system this program is running on.
+    ///
+    /// This is [`LibdirConvention::Multiarch`] on Debian (if the multiarch tuple of the current target is known),
+    ///  [`LibdirConvention::Lib64`] if `/usr/lib64` is a directory which is not a symlink, and [`LibdirConvention::Lib`] otherwise.
+    pub fn detect() -> Self {
+        if Path::new("/etc/debian_version").exists() {
+            if let Some(tuple) = multiarch_tuple() {
+                return LibdirConvention::Multiarch(tuple.to_string());
+            }
+        }
+        match std::fs::symlink_metadata("/usr/lib64") {
+            Ok(meta) if meta.is_dir() => LibdirConvention::Lib64,
+            _ => LibdirConvention::Lib,
+        }
+    }
+
+    ///
+    /// The value of `libdir` in this convention, relative to `exec_prefix`
+    pub fn libdir(&self) -> PathBuf {
+        match self {
+            LibdirConvention::Lib => PathBuf::from("lib"),
+            LibdirConvention::Lib64 => PathBuf::from("lib64"),
+            LibdirConvention::Multiarch(tuple) => Path::new("lib").join(tuple),
+        }
+    }
+}
+
+///
+/// The multiarch tuple of the target this program was compiled for, if it is known
+fn multiarch_tuple() -> Option<&'static str> {
+    let tuple = if cfg!(all(target_arch = "x86_64", target_pointer_width = "64")) {
+        "x86_64-linux-gnu"
+    } else if cfg!(target_arch = "x86_64") {
+        "x86_64-linux-gnux32"
+    } else if cfg!(target_arch = "x86") {
+        "i386-linux-gnu"
+    } else if cfg!(target_arch = "aarch64") {
+        "aarch64-linux-gnu"
+    } else if cfg!(all(target_arch = "arm", target_abi = "eabihf")) {
+        "arm-linux-gnueabihf"
+    } else if cfg!(target_arch = "arm") {
+        "arm-linux-gnueabi"
+    } else if cfg!(all(target_arch = "powerpc64", target_endian = "little")) {
+        "powerpc64le-linux-gnu"
+    } else if cfg!(target_arch = "riscv64") {
+        "riscv64-linux-gnu"
+    } else if cfg!(target_arch = "s390x") {
+        "s390x-linux-gnu"
+    } else {
+        return None;
+    };
+    if cfg!(all(target_os = "linux", target_env = "gnu")) {
+        Some(tuple)
+    } else {
+        None
+    }
+}
+
+impl Layout {
+    ///
+    /// Returns the CMake layout, with the [`LibdirConvention`] detected from the current system
+    pub fn cmake() -> Self {
+        Layout::CMake {
+            libdir: LibdirConvention::detect(),
+        }
+    }
+
+    ///
+    /// Returns the Meson layout, with the [`LibdirConvention`] detected from the current system
+    pub fn meson() -> Self {
+        Layout::Meson {
+            libdir: LibdirConvention::detect(),
+        }
+    }
+}
+
+impl LayoutPolicy for Layout {
+    fn defaults(&self) -> InstallDirs {
+        match self {
+            Layout::Meson { libdir } => InstallDirs {
+                libdir: libdir.libdir(),
+                datadir: "share".into(),
+                mandir: "share/man".into(),
+                infodir: "share/info".into(),
+                localedir: "share/locale".into(),
+                ..InstallDirs::defaults()
+            },
+            _ => InstallDirs::defaults(),
+        }
+    }
+
+    fn base(&self, dir: Dir) -> Option<Dir> {
+        match (self, dir) {
+            (Layout::Default, Dir::Includedir) => Some(Dir::ExecPrefix),
+            _ => dir.base(),
+        }
+    }
+
+    fn resolve(&self, dir: Dir, cx: &LayoutContext) -> PathBuf {
+        match self {
+            Layout::Default => resolve_default(dir, cx),
+            Layout::Gnu => join(cx.resolved(dir.base().unwrap_or(Dir::Prefix)), cx.raw(dir)),
+            Layout::CMake { libdir } => resolve_cmake(dir, cx, libdir),
+            Layout::Meson { .. } => resolve_meson(dir, cx),
+        }
+    }
+
+    fn permits(&self, dir: Dir, val: &Path, prefix: &Path) -> bool {
+        match self {
+            Layout::Meson { .. } => {
+                !is_meson_dir(dir)
+                    || matches!(
+                        dir,
+                        Dir::Sysconfdir | Dir::Localstatedir | Dir::Sharedstatedir
+                    )
+                    || val.starts_with(prefix)
+            }
+            _ => true,
+        }
+    }
+}
+
+fn join(base: &Path, val: &Path) -> PathBuf {
+    if val.as_os_str().is_empty() {
+        base.to_path_buf()
+    } else {
+        base.join(val)
+    }
+}
+
+fn is_usr(prefix: &Path) -> bool {
+    prefix == Path::new("/usr")
+}
+
+fn resolve_default(dir: Dir, cx: &LayoutContext) -> PathBuf {
+    let val = cx.raw(dir);
+    let prefix = cx.prefix();
+    let data_prefix = if prefix == Path::new("/") {
+        Path::new("/usr")
+    } else {
+        prefix
+    };
+    let state_prefix = if prefix.starts_with("/usr") {
+        Path::new("/")
+    } else {
+        prefix
+    };
+
+    match dir {
+        Dir::Includedir => cx.resolved(Dir::ExecPrefix).join(val),
+        Dir::Datarootdir | Dir::Sharedstatedir => data_prefix.join(val),
+        Dir::Sysconfdir | Dir::Localstatedir | Dir::Runstatedir => {
+            if state_prefix.starts_with("/opt") {
+                let mut path = PathBuf::from("/");
+                path.push(val);
+                path.push(without_root(state_prefix));
+                path
+            } else {
+                state_prefix.join(val)
+            }
+        }
+        _ => cx.resolved(dir.base().unwrap_or(Dir::Prefix)).join(val),
+    }
+}
+
+fn resolve_cmake(dir: Dir, cx: &LayoutContext, libdir: &LibdirConvention) -> PathBuf {
+    let prefix = cx.prefix();
+
+    // The value relative to `prefix`, or an absolute path if a base has an absolute value
+    let mut rel = PathBuf::new();
+    let mut cur = dir;
+    loop {
+        let val = cx.raw(cur);
+        let val = if cur == Dir::Libdir && val == Path::new("lib") {
+            match libdir {
+                LibdirConvention::Multiarch(_) if !is_usr(prefix) => val.to_path_buf(),
+                libdir => libdir.libdir(),
+            }
+        } else {
+            val.to_path_buf()
+        };
+        rel = join(&val, &rel);
+        match cur.base() {
+            None | Some(Dir::Prefix) => break,
+            Some(base) if cx.raw(base).is_absolute() => return join(cx.resolved(base), &rel),
+            Some(base) => cur = base,
+        }
+    }
+
+    let state = matches!(dir, Dir::Sysconfdir | Dir::Localstatedir | Dir::Runstatedir);
+    if prefix == Path::new("/") {
+        if state || (rel.starts_with("usr") && rel != Path::new("usr")) {
+            join(prefix, &rel)
+        } else {
+            join(&prefix.join("usr"), &rel)
+        }
+    } else if state && is_usr(prefix) {
+        join(Path::new("/"), &rel)
+    } else if state && prefix.starts_with("/opt") && prefix != Path::new("/opt") {
+        join(&join(Path::new("/"), &rel), &without_root(prefix))
+    } else {
+        join(prefix, &rel)
+    }
+}
+
+fn is_meson_dir(dir: Dir) -> bool {
+    matches!(
+        dir,
+        Dir::Bindir
+            | Dir::Sbindir
+            | Dir::Libdir
+            | Dir::Libexecdir
+            | Dir::Includedir
+            | Dir::Datadir
+            | Dir::Mandir
+            | Dir::Infodir
+            | Dir::Localedir
+            | Dir::Localstatedir
+            | Dir::Sharedstatedir
+            | Dir::Sysconfdir
+    )
+}
+
+fn resolve_meson(dir: Dir, cx: &LayoutContext) -> PathBuf {
+    let val = cx.raw(dir);
+    let prefix = cx.prefix();
+    let usr = is_usr(prefix);
+    let usr_local = prefix == Path::new("/usr/local");
+    let special = match (dir, val.to_str()) {
+        (Dir::Sysconfdir, Some("etc")) if usr => Some("/etc"),
+        (Dir::Localstatedir, Some("var")) if usr => Some("/var"),
+        (Dir::Localstatedir, Some("var")) if usr_local => Some("/var/local"),
+        (Dir::Sharedstatedir, Some("com")) if usr => Some("/var/lib"),
+        (Dir::Sharedstatedir, Some("com")) if usr_local => Some("/var/local/lib"),
+        _ => None,
+    };
+
+    if let Some(path) = special {
+        PathBuf::from(path)
+    } else if is_meson_dir(dir) {
+        join(prefix, val)
+    } else {
+        join(cx.resolved(dir.base().unwrap_or(Dir::Prefix)), val)
+    }
+}
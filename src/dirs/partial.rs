@@ -0,0 +1,213 @@
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use super::{
+    args::parse_args_with, env_settings, ArgError, Dir, EnvNaming, EnvSource, InstallDirs, Setting,
+};
+
+///
+/// A sparse set of install directories, where each directory may or may not be set.
+///
+/// This can be used to describe an overlay, such as a site-wide configuration that only sets `libdir`,
+///  which can then be applied on top of any base configuration with [`PartialInstallDirs::apply_to`].
+///
+/// ## Example
+///
+/// ```
+/// use install_dirs::dirs::{Dir, InstallDirs, PartialInstallDirs};
+/// let mut overlay = PartialInstallDirs::new();
+/// overlay.set(Dir::Libdir, "lib64");
+/// let mut dirs = InstallDirs::with_project_name("foo");
+/// overlay.apply_to(&mut dirs);
+/// assert_eq!(dirs.libdir.to_str(), Some("lib64"));
+/// assert_eq!(dirs.pkglibdir.to_str(), Some("foo"));
+/// ```
+///
+/// With the `serde` feature, self-describing formats omit the directories (and `destdir` and `sysroot`) which are not set,
+///  while binary formats store every directory, so that they can be read back in order.
+///
+/// ```
+/// # #[cfg(feature = "serde")] {
+/// use install_dirs::dirs::{Dir, InstallDirs, PartialInstallDirs};
+/// let mut overlay = PartialInstallDirs::new();
+/// overlay.set(Dir::Libdir, "lib64");
+/// overlay.set(Dir::Sysconfdir, "/etc");
+///
+/// let json = serde_json::to_string(&overlay).unwrap();
+/// assert_eq!(json, r#"{"libdir":"lib64","sysconfdir":"/etc"}"#);
+/// assert_eq!(serde_json::from_str::<PartialInstallDirs>(&json).unwrap(), overlay);
+///
+/// let bytes = bincode::serialize(&overlay).unwrap();
+/// assert_eq!(bincode::deserialize::<PartialInstallDirs>(&bytes).unwrap(), overlay);
+///
+/// let overlay: PartialInstallDirs =
+///     serde_json::from_str(r#"{"destdir":"/tmp/stage","sysroot":"/opt/sysroots/aarch64"}"#).unwrap();
+/// let mut dirs = InstallDirs::defaults();
+/// overlay.apply_to(&mut dirs);
+/// assert_eq!(dirs.destdir.unwrap().to_str(), Some("/tmp/stage"));
+/// assert_eq!(dirs.sysroot.unwrap().to_str(), Some("/opt/sysroots/aarch64"));
+/// # }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct PartialInstallDirs {
+    pub prefix: Option<PathBuf>,
+    pub exec_prefix: Option<PathBuf>,
+    pub bindir: Option<PathBuf>,
+    pub sbindir: Option<PathBuf>,
+    pub libdir: Option<PathBuf>,
+    pub libexecdir: Option<PathBuf>,
+    pub includedir: Option<PathBuf>,
+    pub oldincludedir: Option<PathBuf>,
+    pub datarootdir: Option<PathBuf>,
+    pub datadir: Option<PathBuf>,
+    pub mandir: Option<PathBuf>,
+    pub man1dir: Option<PathBuf>,
+    pub man2dir: Option<PathBuf>,
+    pub man3dir: Option<PathBuf>,
+    pub man4dir: Option<PathBuf>,
+    pub man5dir: Option<PathBuf>,
+    pub man6dir: Option<PathBuf>,
+    pub man7dir: Option<PathBuf>,
+    pub man8dir: Option<PathBuf>,
+    pub man9dir: Option<PathBuf>,
+    pub manext: Option<PathBuf>,
+    pub docdir: Option<PathBuf>,
+    pub htmldir: Option<PathBuf>,
+    pub dvidir: Option<PathBuf>,
+    pub pdfdir: Option<PathBuf>,
+    pub psdir: Option<PathBuf>,
+    pub infodir: Option<PathBuf>,
+    pub localedir: Option<PathBuf>,
+    pub lispdir: Option<PathBuf>,
+    pub localstatedir: Option<PathBuf>,
+    pub runstatedir: Option<PathBuf>,
+    pub sharedstatedir: Option<PathBuf>,
+    pub sysconfdir: Option<PathBuf>,
+    pub pkgdatadir: Option<PathBuf>,
+    pub pkglibdir: Option<PathBuf>,
+    pub pkgincludedir: Option<PathBuf>,
+    pub pkglibexecdir: Option<PathBuf>,
+    /// The staging root, see [`InstallDirs::destdir`]
+    pub destdir: Option<PathBuf>,
+    /// The root of the target system, see [`InstallDirs::sysroot`]
+    pub sysroot: Option<PathBuf>,
+}
+
+impl PartialInstallDirs {
+    ///
+    /// Returns a value with no directories set
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///
+    /// Sets the value of `dir` to `val`
+    pub fn set<P: Into<PathBuf>>(&mut self, dir: Dir, val: P) {
+        *self.get_mut(dir) = Some(val.into());
+    }
+
+    ///
+    /// Unsets `dir`, returning its previous value
+    pub fn unset(&mut self, dir: Dir) -> Option<PathBuf> {
+        self.get_mut(dir).take()
+    }
+
+    ///
+    /// Whether nothing is set
+    pub fn is_empty(&self) -> bool {
+        self.iter().next().is_none() && self.destdir.is_none() && self.sysroot.is_none()
+    }
+
+    ///
+    /// Obtains an iterator over each directory that is set and its value, in the order of [`Dir::ALL`]
+    pub fn iter(&self) -> impl Iterator<Item = (Dir, &Path)> + '_ {
+        Dir::ALL
+            .iter()
+            .filter_map(move |&dir| Some((dir, self.get(dir)?)))
+    }
+
+    ///
+    /// Merges `other` into `self`. Directories (and `destdir` and `sysroot`) set in `other` override those in `self`.
+    pub fn merge(&mut self, mut other: PartialInstallDirs) {
+        for &dir in Dir::ALL {
+            if let Some(val) = other.unset(dir) {
+                self.set(dir, val);
+            }
+        }
+        if other.destdir.is_some() {
+            self.destdir = other.destdir;
+        }
+        if other.sysroot.is_some() {
+            self.sysroot = other.sysroot;
+        }
+    }
+
+    ///
+    /// Overrides each directory (and `destdir` and `sysroot`) in `dirs` that is set in `self`
+    pub fn apply_to(&self, dirs: &mut InstallDirs) {
+        for (dir, val) in self.iter() {
+            dirs.set(dir, val);
+        }
+        if let Some(destdir) = &self.destdir {
+            dirs.destdir = Some(destdir.clone());
+        }
+        if let Some(sysroot) = &self.sysroot {
+            dirs.sysroot = Some(sysroot.clone());
+        }
+    }
+
+    ///
+    /// Reads each directory from the variable in `env` given by `naming`, if it is set,
+    ///  and `destdir` and `sysroot` as described by [`InstallDirs::read_env_named`]
+    pub fn read_env_named<E: EnvSource + ?Sized>(&mut self, env: &E, naming: &EnvNaming) {
+        for (setting, _, val) in env_settings(env, naming) {
+            self.set_setting(setting, val.into());
+        }
+    }
+
+    ///
+    /// Returns the directories set by the variables in `env`, named according to `naming`
+    pub fn from_env<E: EnvSource + ?Sized>(env: &E, naming: &EnvNaming) -> Self {
+        let mut dirs = Self::new();
+        dirs.read_env_named(env, naming);
+        dirs
+    }
+
+    ///
+    /// Parses the directory options from `args`, and returns the arguments that were not consumed.
+    /// See [`InstallDirs::parse_args`] for the accepted forms.
+    pub fn parse_args<I>(&mut self, args: I) -> Result<Vec<OsString>, ArgError>
+    where
+        I: IntoIterator,
+        I::Item: Into<OsString>,
+    {
+        parse_args_with(args, |setting, _, val| {
+            self.set_setting(setting, val.into())
+        })
+    }
+
+    ///
+    /// Sets `setting` to `val`
+    fn set_setting(&mut self, setting: Setting, val: PathBuf) {
+        match setting {
+            Setting::Dir(dir) => self.set(dir, val),
+            Setting::Destdir => self.destdir = Some(val),
+            Setting::Sysroot => self.sysroot = Some(val),
+        }
+    }
+}
+
+impl From<InstallDirs> for PartialInstallDirs {
+    fn from(dirs: InstallDirs) -> Self {
+        let mut partial = Self::new();
+        for (dir, val) in dirs.iter() {
+            partial.set(dir, val);
+        }
+        partial.destdir = dirs.destdir;
+        partial.sysroot = dirs.sysroot;
+        partial
+    }
+}
@@ -0,0 +1,217 @@
+use std::{
+    collections::VecDeque,
+    ffi::OsString,
+    io,
+    path::{Component, Path, PathBuf},
+};
+
+use super::{without_root, CanonicalInstallDirs, Dir, InstallDirs};
+
+/// The maximum number of symlinks followed while resolving a single path, as with `MAXSYMLINKS` on Linux
+const MAX_SYMLINKS: usize = 40;
+
+///
+/// A set of canonical install directories with symlinks resolved against a root directory, see [`CanonicalInstallDirs::resolve_symlinks`].
+///
+/// Each directory is the path of the physical directory, as seen from inside the root.
+///
+/// ## Example
+///
+/// ```
+/// # #[cfg(unix)] {
+/// use install_dirs::dirs::{Dir, InstallDirs};
+/// let root = std::env::temp_dir().join(format!("install-dirs-doc-{}", std::process::id()));
+/// std::fs::create_dir_all(root.join("usr/bin")).unwrap();
+/// std::fs::create_dir_all(root.join("usr/lib")).unwrap();
+/// std::os::unix::fs::symlink("usr/bin", root.join("bin")).unwrap();
+/// std::os::unix::fs::symlink("usr/lib", root.join("lib")).unwrap();
+/// std::os::unix::fs::symlink("bin", root.join("usr/sbin")).unwrap();
+///
+/// let mut dirs = InstallDirs::defaults();
+/// dirs.prefix = "/usr".into();
+/// dirs.libdir = "/lib".into();
+/// let physical = dirs.canonicalize().unwrap().resolve_symlinks(&root).unwrap();
+/// assert!(physical.is_usr_merged());
+/// assert_eq!(physical.libdir().to_str(), Some("/usr/lib"));
+/// assert!(physical.aliases().contains(&vec![Dir::Bindir, Dir::Sbindir]));
+/// std::fs::remove_dir_all(&root).unwrap();
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PhysicalDirs {
+    root: PathBuf,
+    canonical: CanonicalInstallDirs,
+    dirs: InstallDirs,
+    usr_merged: bool,
+}
+
+impl PhysicalDirs {
+    ///
+    /// The root directory symlinks were resolved against
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    ///
+    /// The canonical directories, before symlinks were resolved
+    pub fn canonical(&self) -> &CanonicalInstallDirs {
+        &self.canonical
+    }
+
+    ///
+    /// Returns the physical path of `dir`
+    pub fn get(&self, dir: Dir) -> &Path {
+        self.dirs.get(dir)
+    }
+
+    ///
+    /// Obtains an iterator over every directory and its physical path, in the order of [`Dir::ALL`]
+    pub fn iter(&self) -> impl Iterator<Item = (Dir, &Path)> + '_ {
+        self.dirs.iter()
+    }
+
+    ///
+    /// Whether the root has a merged `/usr`, where `/bin` and `/lib` are symlinks to `/usr/bin` and `/usr/lib`
+    pub fn is_usr_merged(&self) -> bool {
+        self.usr_merged
+    }
+
+    ///
+    /// Whether `a` and `b` have different canonical paths, but are the same physical directory
+    pub fn is_alias(&self, a: Dir, b: Dir) -> bool {
+        a.is_path()
+            && b.is_path()
+            && self.canonical.get(a) != self.canonical.get(b)
+            && self.get(a) == self.get(b)
+    }
+
+    ///
+    /// Returns each group of directories which are the same physical directory, but which do not all have the same canonical path,
+    ///  such as `bindir` and `sbindir` on a system where `/usr/sbin` is a symlink to `/usr/bin`.
+    /// Installing a file to each directory in a group would overwrite the same file.
+    ///
+    /// The directories in each group, and the groups themselves, are in the order of [`Dir::ALL`].
+    pub fn aliases(&self) -> Vec<Vec<Dir>> {
+        let mut groups: Vec<Vec<Dir>> = Vec::new();
+        for &dir in Dir::ALL.iter().filter(|dir| dir.is_path()) {
+            match groups
+                .iter_mut()
+                .find(|group| self.get(group[0]) == self.get(dir))
+            {
+                Some(group) => group.push(dir),
+                None => groups.push(vec![dir]),
+            }
+        }
+        groups.retain(|group| {
+            group
+                .iter()
+                .any(|&dir| self.canonical.get(dir) != self.canonical.get(group[0]))
+        });
+        groups
+    }
+}
+
+impl CanonicalInstallDirs {
+    ///
+    /// Resolves symlinks in each directory against `root`, which is treated as the root directory of the system being installed to,
+    ///  such as `/` or the root of a container image.
+    ///
+    /// Only the part of each path which exists is resolved, and the rest is kept as it is.
+    /// A symlink with an absolute target is resolved relative to `root`, and `..` does not go above `root`.
+    ///
+    /// Returns an error if a symlink cannot be read, or if too many symlinks are followed while resolving one directory.
+    pub fn resolve_symlinks<P: AsRef<Path>>(&self, root: P) -> io::Result<PhysicalDirs> {
+        let root = root.as_ref();
+        let mut dirs = self.as_install_dirs().clone();
+        for &dir in Dir::ALL {
+            if dir.is_path() {
+                let physical = resolve_in_root(root, self.get(dir))?;
+                dirs.set(dir, physical);
+            }
+        }
+
+        let usr_merged = resolve_in_root(root, Path::new("/bin"))? == Path::new("/usr/bin")
+            && resolve_in_root(root, Path::new("/lib"))? == Path::new("/usr/lib");
+
+        Ok(PhysicalDirs {
+            root: root.to_path_buf(),
+            canonical: self.clone(),
+            dirs,
+            usr_merged,
+        })
+    }
+}
+
+///
+/// Resolves the symlinks in the absolute `path`, as seen from inside `root`
+fn resolve_in_root(root: &Path, path: &Path) -> io::Result<PathBuf> {
+    let mut resolved = PathBuf::new();
+    let mut remaining: VecDeque<OsString> = VecDeque::new();
+    let mut links = 0;
+
+    for component in path.components() {
+        match component {
+            Component::Prefix(_) | Component::RootDir => resolved.push(component),
+            component => remaining.push_back(component.as_os_str().to_os_string()),
+        }
+    }
+    let top = resolved.clone();
+
+    // Once a component does not exist, the rest of the path is only normalized
+    let mut exists = true;
+    while let Some(name) = remaining.pop_front() {
+        match Path::new(&name).components().next() {
+            None | Some(Component::CurDir) => continue,
+            Some(Component::ParentDir) => {
+                if resolved != top {
+                    resolved.pop();
+                }
+                continue;
+            }
+            _ if !exists => {
+                resolved.push(&name);
+                continue;
+            }
+            _ => {}
+        }
+
+        let candidate = resolved.join(&name);
+        let meta = match std::fs::symlink_metadata(root.join(without_root(&candidate))) {
+            Ok(meta) => meta,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                resolved = candidate;
+                exists = false;
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+
+        if !meta.file_type().is_symlink() {
+            // Nothing exists below a file which is not a directory
+            exists = meta.is_dir();
+            resolved = candidate;
+            continue;
+        }
+
+        links += 1;
+        if links > MAX_SYMLINKS {
+            return Err(io::Error::other(format!(
+                "too many levels of symbolic links in {}",
+                path.display()
+            )));
+        }
+
+        let target = std::fs::read_link(root.join(without_root(&candidate)))?;
+        if target.has_root() {
+            resolved = top.clone();
+        }
+        for component in target.components().rev() {
+            match component {
+                Component::Prefix(_) | Component::RootDir => {}
+                component => remaining.push_front(component.as_os_str().to_os_string()),
+            }
+        }
+    }
+
+    Ok(resolved)
+}
@@ -0,0 +1,178 @@
+use std::path::{Path, PathBuf};
+
+use super::{
+    normalize, CanonicalInstallDirs, CanonicalizationError, CanonicalizationErrorKind,
+    CanonicalizationIssue, Dir, ParentDirs,
+};
+
+///
+/// Canonical install directories which have been moved to a new prefix by [`CanonicalInstallDirs::rebase`],
+///  along with the directories which could not be moved
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rebased {
+    dirs: CanonicalInstallDirs,
+    not_relocated: Vec<Dir>,
+}
+
+impl Rebased {
+    ///
+    /// The rebased directories
+    pub fn dirs(&self) -> &CanonicalInstallDirs {
+        &self.dirs
+    }
+
+    ///
+    /// Discards the directories which could not be moved, and returns the rebased directories
+    pub fn into_dirs(self) -> CanonicalInstallDirs {
+        self.dirs
+    }
+
+    ///
+    /// The directories which were not under the old prefix, and so were left where they were, in the order of [`Dir::ALL`]
+    pub fn not_relocated(&self) -> &[Dir] {
+        &self.not_relocated
+    }
+}
+
+impl CanonicalInstallDirs {
+    ///
+    /// Moves every directory under `old_prefix` to the same place under `new_prefix`, as with `make install prefix=/other`.
+    /// This includes `exec_prefix` and the directories under it, if `exec_prefix` is under `old_prefix`.
+    /// To move an `exec_prefix` which is elsewhere, use [`CanonicalInstallDirs::rebase_with_exec_prefix`].
+    ///
+    /// Directories outside of `old_prefix`, such as a `sysconfdir` of `/etc`, are left unchanged and reported by [`Rebased::not_relocated`].
+    /// Unexpanded values which are absolute paths under `old_prefix` are moved in the same way, while relative and symbolic values, such as `${prefix}/lib`, are kept.
+    ///
+    /// Returns an error if `new_prefix` is not an absolute path.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use install_dirs::dirs::{Dir, InstallDirs};
+    /// let mut dirs = InstallDirs::defaults();
+    /// dirs.prefix = "/usr/local".into();
+    /// dirs.sysconfdir = "/etc".into();
+    /// let dirs = dirs.canonicalize().unwrap();
+    /// let rebased = dirs.rebase("/usr/local", "/opt/foo").unwrap();
+    /// assert_eq!(rebased.dirs().libdir().to_str(), Some("/opt/foo/lib"));
+    /// assert_eq!(rebased.dirs().sysconfdir().to_str(), Some("/etc"));
+    /// assert!(rebased.not_relocated().contains(&Dir::Sysconfdir));
+    /// ```
+    pub fn rebase<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        old_prefix: P,
+        new_prefix: Q,
+    ) -> Result<Rebased, CanonicalizationError> {
+        let new_prefix = absolute_prefix(Dir::Prefix, new_prefix.as_ref())
+            .map_err(|issue| CanonicalizationError::new(vec![issue]))?;
+        Ok(self.rebase_onto(&[(old_prefix.as_ref(), &new_prefix)]))
+    }
+
+    ///
+    /// Moves every directory under `old_exec_prefix` to the same place under `new_exec_prefix`,
+    ///  and every other directory under `old_prefix` to the same place under `new_prefix`,
+    ///  as with `make install prefix=/other exec_prefix=/other-exec`. See [`CanonicalInstallDirs::rebase`].
+    ///
+    /// A directory which is under both, such as `bindir` when `exec_prefix` is under `prefix`, is moved according to the longer of the two.
+    ///
+    /// Returns an error if `new_prefix` or `new_exec_prefix` is not an absolute path.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use install_dirs::dirs::{Dir, InstallDirs};
+    /// let mut dirs = InstallDirs::defaults();
+    /// dirs.prefix = "/srv/local".into();
+    /// dirs.exec_prefix = "/srv/local-x86_64".into();
+    /// let dirs = dirs.canonicalize().unwrap();
+    /// let rebased = dirs
+    ///     .rebase_with_exec_prefix("/srv/local", "/opt/foo", "/srv/local-x86_64", "/opt/foo/x86_64")
+    ///     .unwrap();
+    /// assert_eq!(rebased.dirs().libdir().to_str(), Some("/opt/foo/x86_64/lib"));
+    /// assert_eq!(rebased.dirs().datadir().to_str(), Some("/opt/foo/share"));
+    /// assert_eq!(rebased.not_relocated(), [Dir::Oldincludedir]);
+    /// ```
+    pub fn rebase_with_exec_prefix<P, Q, R, S>(
+        &self,
+        old_prefix: P,
+        new_prefix: Q,
+        old_exec_prefix: R,
+        new_exec_prefix: S,
+    ) -> Result<Rebased, CanonicalizationError>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+        R: AsRef<Path>,
+        S: AsRef<Path>,
+    {
+        let new_prefix = absolute_prefix(Dir::Prefix, new_prefix.as_ref());
+        let new_exec_prefix = absolute_prefix(Dir::ExecPrefix, new_exec_prefix.as_ref());
+        match (new_prefix, new_exec_prefix) {
+            (Ok(new_prefix), Ok(new_exec_prefix)) => Ok(self.rebase_onto(&[
+                (old_prefix.as_ref(), &new_prefix),
+                (old_exec_prefix.as_ref(), &new_exec_prefix),
+            ])),
+            (new_prefix, new_exec_prefix) => Err(CanonicalizationError::new(
+                new_prefix
+                    .err()
+                    .into_iter()
+                    .chain(new_exec_prefix.err())
+                    .collect(),
+            )),
+        }
+    }
+
+    ///
+    /// Moves each directory according to the mapping in `prefixes` whose old prefix is the longest one the directory is under
+    fn rebase_onto(&self, prefixes: &[(&Path, &Path)]) -> Rebased {
+        let relocate_any = |path: &Path| {
+            prefixes
+                .iter()
+                .filter(|(old, _)| path.is_absolute() && path.starts_with(old))
+                .max_by_key(|(old, _)| old.components().count())
+                .and_then(|(old, new)| relocate(path, old, new))
+        };
+
+        let (mut dirs, mut unexpanded) = self.clone().into_parts();
+        let mut not_relocated = Vec::new();
+        for &dir in Dir::ALL.iter().filter(|dir| dir.is_path()) {
+            match relocate_any(dirs.get(dir)) {
+                Some(path) => dirs.set(dir, path),
+                None => not_relocated.push(dir),
+            }
+            if let Some(path) = relocate_any(unexpanded.get(dir)) {
+                unexpanded.set(dir, path);
+            }
+        }
+
+        Rebased {
+            dirs: CanonicalInstallDirs::new(dirs, unexpanded),
+            not_relocated,
+        }
+    }
+}
+
+///
+/// Normalizes the new value of `dir`, which must be an absolute path
+fn absolute_prefix(dir: Dir, path: &Path) -> Result<PathBuf, CanonicalizationIssue> {
+    match normalize(path, ParentDirs::Clamp) {
+        Some(normalized) if normalized.is_absolute() => Ok(normalized),
+        _ => Err(CanonicalizationIssue::new(
+            dir,
+            path,
+            CanonicalizationErrorKind::RelativePrefix,
+        )),
+    }
+}
+
+///
+/// Returns `path` moved from under `old_prefix` to under `new_prefix`, or `None` if it is not under `old_prefix`
+fn relocate(path: &Path, old_prefix: &Path, new_prefix: &Path) -> Option<PathBuf> {
+    if !path.is_absolute() {
+        return None;
+    }
+    let rest = path.strip_prefix(old_prefix).ok()?;
+    let mut out = new_prefix.to_path_buf();
+    out.extend(rest.components());
+    Some(out)
+}
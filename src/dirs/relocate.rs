@@ -0,0 +1,216 @@
+use std::path::{Path, PathBuf};
+
+use super::{CanonicalInstallDirs, CanonicalizationError, Dir, InstallDirs};
+
+/// The directories an executable may be installed to, in the order they are tried
+const EXE_DIRS: &[Dir] = &[
+    Dir::Bindir,
+    Dir::Sbindir,
+    Dir::Libexecdir,
+    Dir::Pkglibexecdir,
+];
+
+/// The directories a shared library may be installed to, in the order they are tried
+const LIBRARY_DIRS: &[Dir] = &[Dir::Libdir, Dir::Pkglibdir];
+
+impl InstallDirs {
+    ///
+    /// Works out the prefix the executable at `exe` was installed under, from where `bindir` (or `sbindir`, `libexecdir` or `pkglibexecdir`)
+    ///  is relative to `prefix` in these directories once canonicalized.
+    ///
+    /// Returns `None` if these directories cannot be canonicalized, if `exe` is not an absolute path,
+    ///  or if `exe` is not in any of those directories under some prefix.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use install_dirs::dirs::InstallDirs;
+    /// let dirs = InstallDirs::with_project_name("foo");
+    /// assert_eq!(
+    ///     dirs.prefix_for_exe("/home/me/apps/foo-1.0/bin/foo").unwrap().to_str(),
+    ///     Some("/home/me/apps/foo-1.0")
+    /// );
+    /// assert_eq!(
+    ///     dirs.prefix_for_exe("/home/me/apps/foo-1.0/libexec/foo/helper").unwrap().to_str(),
+    ///     Some("/home/me/apps/foo-1.0")
+    /// );
+    /// assert_eq!(dirs.prefix_for_exe("/home/me/foo"), None);
+    /// assert_eq!(dirs.prefix_for_exe("bin/foo"), None);
+    /// ```
+    pub fn prefix_for_exe<P: AsRef<Path>>(&self, exe: P) -> Option<PathBuf> {
+        let canonical = self.clone().canonicalize().ok()?;
+        prefix_for_file(&canonical, exe.as_ref(), EXE_DIRS)
+    }
+
+    ///
+    /// Returns these directories, canonicalized and moved to where the executable at `exe` was actually installed, see [`InstallDirs::prefix_for_exe`].
+    /// Directories outside of `prefix` (such as a `sysconfdir` of `/etc`) are not moved.
+    ///
+    /// Returns `None` if these directories cannot be canonicalized, or if the prefix cannot be worked out from `exe`.
+    pub fn relocate_for_exe<P: AsRef<Path>>(&self, exe: P) -> Option<CanonicalInstallDirs> {
+        let canonical = self.clone().canonicalize().ok()?;
+        relocate(&canonical, exe.as_ref(), EXE_DIRS)
+    }
+
+    ///
+    /// Returns these directories moved to where the running executable was actually installed, following any symlinks to it.
+    /// This allows a program built with the directories from [`parse_env!`](crate::parse_env) to be moved after it is installed,
+    ///  such as in a relocatable tarball or an AppImage, or with `cargo install --root`.
+    ///
+    /// If the location of the executable cannot be found, or it is not in `bindir` (or `sbindir`, `libexecdir` or `pkglibexecdir`) under some prefix,
+    ///  these directories are returned where they were compiled in.
+    /// Either way the result is canonical, so the paths are absolute regardless of where the program is installed.
+    ///
+    /// Returns an error if these directories cannot be canonicalized.
+    pub fn relocate_to_current_exe(&self) -> Result<CanonicalInstallDirs, CanonicalizationError> {
+        let canonical = self.clone().canonicalize()?;
+        Ok(current_exe()
+            .and_then(|exe| relocate(&canonical, &exe, EXE_DIRS))
+            .unwrap_or(canonical))
+    }
+
+    ///
+    /// Works out the prefix the shared library at `library` was installed under, from where `libdir` (or `pkglibdir`)
+    ///  is relative to `prefix` in these directories once canonicalized.
+    ///
+    /// Returns `None` if these directories cannot be canonicalized, if `library` is not an absolute path,
+    ///  or if `library` is not in either of those directories under some prefix.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use install_dirs::dirs::InstallDirs;
+    /// let mut dirs = InstallDirs::with_project_name("foo");
+    /// dirs.libdir = "lib64".into();
+    /// assert_eq!(
+    ///     dirs.prefix_for_library("/opt/foo/lib64/libfoo.so.1").unwrap().to_str(),
+    ///     Some("/opt/foo")
+    /// );
+    /// assert_eq!(dirs.prefix_for_library("/opt/foo/lib/libfoo.so.1"), None);
+    /// ```
+    pub fn prefix_for_library<P: AsRef<Path>>(&self, library: P) -> Option<PathBuf> {
+        let canonical = self.clone().canonicalize().ok()?;
+        prefix_for_file(&canonical, library.as_ref(), LIBRARY_DIRS)
+    }
+
+    ///
+    /// Returns these directories, canonicalized and moved to where the shared library at `library` was actually installed,
+    ///  see [`InstallDirs::prefix_for_library`]. Directories outside of `prefix` are not moved.
+    ///
+    /// Returns `None` if these directories cannot be canonicalized, or if the prefix cannot be worked out from `library`.
+    pub fn relocate_for_library<P: AsRef<Path>>(&self, library: P) -> Option<CanonicalInstallDirs> {
+        let canonical = self.clone().canonicalize().ok()?;
+        relocate(&canonical, library.as_ref(), LIBRARY_DIRS)
+    }
+
+    ///
+    /// Returns these directories moved to where the shared library containing `addr` was actually installed, following any symlinks to it.
+    /// `addr` is the address of any function or static in the library, such as one of its own functions, which finds the calling library
+    ///  rather than the executable it was loaded into. The library is found from the mappings of the process in `/proc/self/maps`.
+    ///
+    /// If the library cannot be found, or it is not in `libdir` (or `pkglibdir`) under some prefix,
+    ///  these directories are returned where they were compiled in.
+    /// Either way the result is canonical, see [`InstallDirs::relocate_to_current_exe`].
+    ///
+    /// Returns an error if these directories cannot be canonicalized.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # #[cfg(target_os = "linux")] {
+    /// use install_dirs::dirs::InstallDirs;
+    /// fn anchor() {}
+    /// let dirs = InstallDirs::with_project_name("foo");
+    /// // `anchor` is in this executable, which is not installed to `libdir`
+    /// assert_eq!(
+    ///     dirs.relocate_to_library_of(anchor as *const ()).unwrap(),
+    ///     dirs.canonicalize().unwrap()
+    /// );
+    /// # }
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn relocate_to_library_of(
+        &self,
+        addr: *const (),
+    ) -> Result<CanonicalInstallDirs, CanonicalizationError> {
+        let canonical = self.clone().canonicalize()?;
+        Ok(mapped_file(addr as usize)
+            .and_then(|library| relocate(&canonical, &library, LIBRARY_DIRS))
+            .unwrap_or(canonical))
+    }
+}
+
+///
+/// Returns the location of the running executable, with symlinks resolved
+fn current_exe() -> Option<PathBuf> {
+    let exe = std::env::current_exe().ok().or_else(|| {
+        if cfg!(target_os = "linux") {
+            std::fs::read_link("/proc/self/exe").ok()
+        } else {
+            None
+        }
+    })?;
+    Some(std::fs::canonicalize(&exe).unwrap_or(exe))
+}
+
+///
+/// Returns the file mapped at `addr` in this process, with symlinks resolved
+#[cfg(target_os = "linux")]
+fn mapped_file(addr: usize) -> Option<PathBuf> {
+    use std::os::unix::ffi::OsStrExt as _;
+    let maps = std::fs::read("/proc/self/maps").ok()?;
+    let path = maps.split(|&b| b == b'\n').find_map(|line| {
+        // Each line is `start-end perms offset dev inode path`, where the path may contain spaces
+        let mut fields = line.splitn(6, |&b| b == b' ');
+        let range = std::str::from_utf8(fields.next()?).ok()?;
+        let (start, end) = range.split_once('-')?;
+        let start = usize::from_str_radix(start, 16).ok()?;
+        let end = usize::from_str_radix(end, 16).ok()?;
+        if !(start..end).contains(&addr) {
+            return None;
+        }
+        let path = fields.nth(4)?;
+        let path = &path[path.iter().position(|&b| b != b' ')?..];
+        if !path.starts_with(b"/") {
+            return None;
+        }
+        let path = path.strip_suffix(b" (deleted)").unwrap_or(path);
+        Some(PathBuf::from(std::ffi::OsStr::from_bytes(path)))
+    })?;
+    Some(std::fs::canonicalize(&path).unwrap_or(path))
+}
+
+///
+/// Returns the prefix `file` was installed under, if `file` is absolute and its parent is one of `dirs` relative to `prefix` in `canonical`
+fn prefix_for_file(canonical: &CanonicalInstallDirs, file: &Path, dirs: &[Dir]) -> Option<PathBuf> {
+    if !file.is_absolute() {
+        return None;
+    }
+    let parent = file.parent()?;
+    dirs.iter().find_map(|&dir| {
+        let rel = canonical.get(dir).strip_prefix(canonical.prefix()).ok()?;
+        let mut prefix = parent;
+        for _ in rel.components() {
+            prefix = prefix.parent()?;
+        }
+        if prefix.join(rel) == parent {
+            Some(prefix.to_path_buf())
+        } else {
+            None
+        }
+    })
+}
+
+///
+/// Returns `canonical` moved to the prefix `file` was installed under, see [`prefix_for_file`]
+fn relocate(
+    canonical: &CanonicalInstallDirs,
+    file: &Path,
+    dirs: &[Dir],
+) -> Option<CanonicalInstallDirs> {
+    let prefix = prefix_for_file(canonical, file, dirs)?;
+    canonical
+        .rebase(canonical.prefix(), prefix)
+        .ok()
+        .map(|rebased| rebased.into_dirs())
+}
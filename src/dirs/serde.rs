@@ -1,63 +1,363 @@
-use super::InstallDirs;
+use std::path::{Path, PathBuf};
+
+use super::{CanonicalInstallDirs, InstallDirs, PartialInstallDirs};
+
+///
+/// Serializes a path losslessly.
+/// Paths which are valid UTF-8 are serialized as strings. Otherwise they are serialized as bytes:
+///  on unix the bytes of the path, and on windows its WTF-8 encoding, which is UTF-8 extended to unpaired surrogates.
+struct SerPath<'a>(&'a Path);
+
+impl ::serde::ser::Serialize for SerPath<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ::serde::ser::Serializer,
+    {
+        if let Some(s) = self.0.to_str() {
+            return serializer.serialize_str(s);
+        }
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt as _;
+            serializer.serialize_bytes(self.0.as_os_str().as_bytes())
+        }
+
+        #[cfg(windows)]
+        {
+            use std::os::windows::ffi::OsStrExt as _;
+            serializer.serialize_bytes(&encode_wtf8(self.0.as_os_str().encode_wide()))
+        }
+
+        #[cfg(not(any(unix, windows)))]
+        {
+            Err(::serde::ser::Error::custom(
+                "path contains invalid UTF-8 characters",
+            ))
+        }
+    }
+}
+
+///
+/// Encodes UTF-16 code units as WTF-8, where each unpaired surrogate is encoded as if it were a character
+#[cfg(windows)]
+fn encode_wtf8<I: IntoIterator<Item = u16>>(wide: I) -> Vec<u8> {
+    let mut out = Vec::new();
+    for c in char::decode_utf16(wide) {
+        match c {
+            Ok(c) => out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+            Err(e) => {
+                let u = e.unpaired_surrogate();
+                out.extend_from_slice(&[
+                    0xE0 | (u >> 12) as u8,
+                    0x80 | ((u >> 6) & 0x3F) as u8,
+                    0x80 | (u & 0x3F) as u8,
+                ]);
+            }
+        }
+    }
+    out
+}
+
+///
+/// Decodes WTF-8 into UTF-16 code units, or returns `None` if `bytes` is not valid WTF-8
+#[cfg(windows)]
+fn decode_wtf8(bytes: &[u8]) -> Option<Vec<u16>> {
+    let mut wide = Vec::with_capacity(bytes.len());
+    let mut rest = bytes;
+    while let Some(&lead) = rest.first() {
+        let (len, min) = match lead {
+            0x00..=0x7F => (1, 0),
+            0xC0..=0xDF => (2, 0x80),
+            0xE0..=0xEF => (3, 0x800),
+            0xF0..=0xF7 => (4, 0x10000),
+            _ => return None,
+        };
+        let seq = rest.get(..len)?;
+        let mut c = u32::from(lead) & if len == 1 { 0x7F } else { 0x7F >> len };
+        for &b in &seq[1..] {
+            if b & 0xC0 != 0x80 {
+                return None;
+            }
+            c = (c << 6) | u32::from(b & 0x3F);
+        }
+        if c < min || c > 0x10FFFF {
+            return None;
+        }
+        if c >= 0x10000 {
+            let c = c - 0x10000;
+            wide.push(0xD800 | (c >> 10) as u16);
+            wide.push(0xDC00 | (c & 0x3FF) as u16);
+        } else {
+            // A surrogate pair must be encoded as the character it stands for
+            if (0xDC00..0xE000).contains(&c)
+                && matches!(wide.last(), Some(&prev) if (0xD800..0xDC00).contains(&prev))
+            {
+                return None;
+            }
+            wide.push(c as u16);
+        }
+        rest = &rest[len..];
+    }
+    Some(wide)
+}
+
+///
+/// Deserializes a path serialized by [`SerPath`].
+///
+/// Self-describing formats may give the path as a string, bytes, or a sequence of bytes.
+/// Other formats are expected to store strings and bytes the same way, and are read as bytes.
+struct DePath(PathBuf);
+
+struct DePathVisitor;
+
+impl<'de> ::serde::de::Visitor<'de> for DePathVisitor {
+    type Value = DePath;
+
+    fn expecting(&self, formatter: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
+        formatter.write_str("a path string or byte sequence")
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<DePath, E>
+    where
+        E: ::serde::de::Error,
+    {
+        Ok(DePath(v.into()))
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<DePath, E>
+    where
+        E: ::serde::de::Error,
+    {
+        Ok(DePath(v.into()))
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<DePath, E>
+    where
+        E: ::serde::de::Error,
+    {
+        self.visit_byte_buf(v.to_vec())
+    }
+
+    fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<DePath, E>
+    where
+        E: ::serde::de::Error,
+    {
+        match String::from_utf8(v) {
+            Ok(s) => Ok(DePath(s.into())),
+            #[cfg(unix)]
+            Err(e) => {
+                use std::os::unix::ffi::OsStringExt as _;
+                Ok(DePath(std::ffi::OsString::from_vec(e.into_bytes()).into()))
+            }
+            #[cfg(windows)]
+            Err(e) => {
+                use std::os::windows::ffi::OsStringExt as _;
+                match decode_wtf8(e.as_bytes()) {
+                    Some(wide) => Ok(DePath(std::ffi::OsString::from_wide(&wide).into())),
+                    None => Err(::serde::de::Error::invalid_value(
+                        ::serde::de::Unexpected::Bytes(e.as_bytes()),
+                        &self,
+                    )),
+                }
+            }
+            #[cfg(not(any(unix, windows)))]
+            Err(e) => Err(::serde::de::Error::invalid_value(
+                ::serde::de::Unexpected::Bytes(e.as_bytes()),
+                &self,
+            )),
+        }
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<DePath, A::Error>
+    where
+        A: ::serde::de::SeqAccess<'de>,
+    {
+        let mut bytes = Vec::new();
+        while let Some(b) = seq.next_element::<u8>()? {
+            bytes.push(b);
+        }
+        self.visit_byte_buf(bytes)
+    }
+}
+
+impl<'de> ::serde::de::Deserialize<'de> for DePath {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: ::serde::de::Deserializer<'de>,
+    {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_any(DePathVisitor)
+        } else {
+            deserializer.deserialize_byte_buf(DePathVisitor)
+        }
+    }
+}
 
 macro_rules! impl_serde{
     {
-        $tyname:ident = $defaults:ident {
+        @fields $tyname:ident {
+            $($field:ident),*
+            $(,)?
+        }
+    } => {
+        const __FIELD_COUNT: usize = (0 $(+(1,::core::stringify!($field)).0)*);
+
+        const __FIELDS: [&'static str; __FIELD_COUNT] = [$(::core::stringify!($field)),*];
+
+        #[allow(non_camel_case_types)]
+        enum __Field{
+            $($field),*
+        }
+
+        struct __FieldVisitor;
+
+        impl<'de> ::serde::de::Visitor<'de> for __FieldVisitor{
+            type Value = __Field;
+
+            #[allow(unused_mut,unused_variables, unused_assignments)]
+            fn expecting(&self, formatter: &mut ::core::fmt::Formatter) -> ::core::fmt::Result{
+                formatter.write_str("one of: ")?;
+                let mut sep = "";
+
+                $({
+                    formatter.write_str(sep)?;
+                    sep = ", ";
+                    formatter.write_str(::core::stringify!($field))?;
+                })*
+
+                Ok(())
+            }
+
+            fn visit_str<__E>(self, value: &str) -> Result<__Field,__E> where __E: ::serde::de::Error{
+                match value{
+                    $(::core::stringify!($field) => Ok(__Field:: $field),)*
+                    value => Err(::serde::de::Error::unknown_field(value, &__FIELDS))
+                }
+            }
+        }
+
+        impl<'de> ::serde::de::Deserialize<'de> for __Field{
+            fn deserialize<__D>(deserializer: __D) -> Result<__Field,__D::Error> where __D: ::serde::de::Deserializer<'de>{
+                deserializer.deserialize_identifier(__FieldVisitor)
+            }
+        }
+
+        impl<'de> ::serde::de::Deserialize<'de> for $tyname{
+            fn deserialize<__D>(deserializer: __D) -> Result<Self, __D::Error> where __D: ::serde::de::Deserializer<'de>{
+                deserializer.deserialize_struct(::core::stringify!($tyname), &__FIELDS, __Visitor)
+            }
+        }
+    };
+    {
+        $tyname:ident = partial {
             $($field:ident),*
             $(,)?
         }
     } => {
         const _: () = {
             use ::serde::ser::SerializeStruct as _;
-            const __FIELD_COUNT: usize = (0 $(+(1,::core::stringify!($field)).0)*);
 
-            const __FIELDS: [&'static str; __FIELD_COUNT] = [$(::core::stringify!($field)),*];
+            impl_serde!(@fields $tyname { $($field),* });
 
             impl ::serde::ser::Serialize for $tyname{
                 fn serialize<__S>(&self, serializer: __S) -> Result<__S::Ok,__S::Error> where __S: ::serde::ser::Serializer{
-                    let mut fields = serializer.serialize_struct(::core::stringify!($tyname),__FIELD_COUNT)?;
+                    // Self-describing formats omit unset fields, others need every field present to read it back as a sequence
+                    let __skip = serializer.is_human_readable();
+                    let __len = if __skip {
+                        0 $(+ self.$field.is_some() as usize)*
+                    } else {
+                        __FIELD_COUNT
+                    };
+                    let mut fields = serializer.serialize_struct(::core::stringify!($tyname),__len)?;
 
-                    $(fields.serialize_field(::core::stringify!($field), &self.$field)?;)*
+                    $(
+                        if __skip && self.$field.is_none() {
+                            fields.skip_field(::core::stringify!($field))?;
+                        } else {
+                            fields.serialize_field(::core::stringify!($field), &self.$field.as_deref().map(SerPath))?;
+                        }
+                    )*
 
                     fields.end()
                 }
             }
 
-            #[allow(non_camel_case_types)]
-            enum __Field{
-                $($field),*
-            }
-
-            struct __FieldVisitor;
+            struct __Visitor;
 
-            impl<'de> ::serde::de::Visitor<'de> for __FieldVisitor{
-                type Value = __Field;
+            impl<'de> ::serde::de::Visitor<'de> for __Visitor{
+                type Value = $tyname;
 
-                #[allow(unused_mut,unused_variables, unused_assignments)]
                 fn expecting(&self, formatter: &mut ::core::fmt::Formatter) -> ::core::fmt::Result{
-                    formatter.write_str("one of: ")?;
-                    let mut sep = "";
+                    formatter.write_str(::core::concat!("struct ", ::core::stringify!($tyname)))
+                }
 
-                    $({
-                        formatter.write_str(sep)?;
-                        sep = ", ";
-                        formatter.write_str(::core::stringify!($field))?;
-                    })*
+                fn visit_seq<__V>(self, mut seq: __V) -> Result<$tyname, __V::Error> where __V: ::serde::de::SeqAccess<'de>{
+                    let mut __length = 0;
+                    $(let $field = seq.next_element::<Option<DePath>>()?.ok_or_else(|| ::serde::de::Error::invalid_length({let __val = __length; __length += 1; __val},&self))?.map(|path| path.0);)*
 
-                    Ok(())
+                    Ok($tyname { $($field,)* ..::core::default::Default::default() })
                 }
 
-                fn visit_str<__E>(self, value: &str) -> Result<__Field,__E> where __E: ::serde::de::Error{
-                    match value{
-                        $(::core::stringify!($field) => Ok(__Field:: $field),)*
-                        value => Err(::serde::de::Error::unknown_field(value, &__FIELDS))
+                fn visit_map<__V>(self, mut map: __V) -> Result<$tyname, __V::Error> where __V: ::serde::de::MapAccess<'de>{
+                    $(let mut $field = None;)*
+
+                    while let Some(key) = map.next_key()? {
+                        match key{
+                            $(__Field :: $field => {
+                                if $field.is_some(){
+                                    return Err(::serde::de::Error::duplicate_field(::core::stringify!($field)));
+                                }
+
+                                $field = Some(map.next_value::<Option<DePath>>()?.map(|path| path.0));
+                            })*
+                        }
                     }
+
+                    Ok($tyname {
+                        $($field: $field.flatten(),)*
+                        ..::core::default::Default::default()
+                    })
                 }
             }
+        };
+    };
+    {
+        $tyname:ident = $defaults:ident {
+            $($field:ident),*
+            $(,)?
+        } optional {
+            $($opt:ident),*
+            $(,)?
+        }
+    } => {
+        const _: () = {
+            use ::serde::ser::SerializeStruct as _;
+
+            impl_serde!(@fields $tyname { $($field,)* $($opt,)* });
 
-            impl<'de> ::serde::de::Deserialize<'de> for __Field{
-                fn deserialize<__D>(deserializer: __D) -> Result<__Field,__D::Error> where __D: ::serde::de::Deserializer<'de>{
-                    deserializer.deserialize_identifier(__FieldVisitor)
+            impl ::serde::ser::Serialize for $tyname{
+                fn serialize<__S>(&self, serializer: __S) -> Result<__S::Ok,__S::Error> where __S: ::serde::ser::Serializer{
+                    // Self-describing formats omit unset optional fields, others need every field present to read it back as a sequence
+                    let __skip = serializer.is_human_readable();
+                    let __len = if __skip {
+                        __FIELD_COUNT $(- self.$opt.is_none() as usize)*
+                    } else {
+                        __FIELD_COUNT
+                    };
+                    let mut fields = serializer.serialize_struct(::core::stringify!($tyname),__len)?;
+
+                    $(fields.serialize_field(::core::stringify!($field), &SerPath(&self.$field))?;)*
+
+                    $(
+                        if __skip && self.$opt.is_none() {
+                            fields.skip_field(::core::stringify!($opt))?;
+                        } else {
+                            fields.serialize_field(::core::stringify!($opt), &self.$opt.as_deref().map(SerPath))?;
+                        }
+                    )*
+
+                    fields.end()
                 }
             }
 
@@ -72,13 +372,15 @@ macro_rules! impl_serde{
 
                 fn visit_seq<__V>(self, mut seq: __V) -> Result<$tyname, __V::Error> where __V: ::serde::de::SeqAccess<'de>{
                     let mut __length = 0;
-                    $(let $field = seq.next_element()?.ok_or_else(|| ::serde::de::Error::invalid_length({let __val = __length; __length += 1; __val},&self))?;)*
+                    $(let $field = seq.next_element::<DePath>()?.ok_or_else(|| ::serde::de::Error::invalid_length({let __val = __length; __length += 1; __val},&self))?.0;)*
+                    $(let $opt = seq.next_element::<Option<DePath>>()?.ok_or_else(|| ::serde::de::Error::invalid_length({let __val = __length; __length += 1; __val},&self))?.map(|path| path.0);)*
 
-                    Ok($tyname { $($field),*})
+                    Ok($tyname { $($field,)* $($opt,)* ..$tyname::$defaults() })
                 }
 
                 fn visit_map<__V>(self, mut map: __V) -> Result<$tyname, __V::Error> where __V: ::serde::de::MapAccess<'de>{
                     $(let mut $field = None;)*
+                    $(let mut $opt = None;)*
 
                     while let Some(key) = map.next_key()? {
                         match key{
@@ -87,7 +389,14 @@ macro_rules! impl_serde{
                                     return Err(::serde::de::Error::duplicate_field(::core::stringify!($field)));
                                 }
 
-                                $field = Some(map.next_value()?);
+                                $field = Some(map.next_value::<DePath>()?.0);
+                            })*
+                            $(__Field :: $opt => {
+                                if $opt.is_some(){
+                                    return Err(::serde::de::Error::duplicate_field(::core::stringify!($opt)));
+                                }
+
+                                $opt = Some(map.next_value::<Option<DePath>>()?.map(|path| path.0));
                             })*
                         }
                     }
@@ -97,18 +406,17 @@ macro_rules! impl_serde{
                     $(
                         let $field = $field.unwrap_or(defaults.$field);
                     )*
+                    $(
+                        let $opt = $opt.unwrap_or(defaults.$opt);
+                    )*
 
                     Ok($tyname {
-                        $($field),*
+                        $($field,)*
+                        $($opt,)*
+                        ..defaults
                     })
                 }
             }
-
-            impl<'de> ::serde::de::Deserialize<'de> for $tyname{
-                fn deserialize<__D>(deserializer: __D) -> Result<Self, __D::Error> where __D: ::serde::de::Deserializer<'de>{
-                    deserializer.deserialize_struct(::core::stringify!($tyname), &__FIELDS, __Visitor)
-                }
-            }
         };
 
     }
@@ -123,15 +431,94 @@ impl_serde! {
         libdir,
         libexecdir,
         includedir,
+        oldincludedir,
+        datarootdir,
+        datadir,
+        mandir,
+        man1dir,
+        man2dir,
+        man3dir,
+        man4dir,
+        man5dir,
+        man6dir,
+        man7dir,
+        man8dir,
+        man9dir,
+        manext,
+        docdir,
+        htmldir,
+        dvidir,
+        pdfdir,
+        psdir,
+        infodir,
+        localedir,
+        lispdir,
+        localstatedir,
+        runstatedir,
+        sharedstatedir,
+        sysconfdir,
+        pkgdatadir,
+        pkglibdir,
+        pkgincludedir,
+        pkglibexecdir,
+    } optional {
+        destdir,
+        sysroot,
+    }
+}
+
+impl_serde! {
+    PartialInstallDirs = partial{
+        prefix,
+        exec_prefix,
+        bindir,
+        sbindir,
+        libdir,
+        libexecdir,
+        includedir,
+        oldincludedir,
         datarootdir,
         datadir,
         mandir,
+        man1dir,
+        man2dir,
+        man3dir,
+        man4dir,
+        man5dir,
+        man6dir,
+        man7dir,
+        man8dir,
+        man9dir,
+        manext,
         docdir,
+        htmldir,
+        dvidir,
+        pdfdir,
+        psdir,
         infodir,
         localedir,
+        lispdir,
         localstatedir,
         runstatedir,
         sharedstatedir,
         sysconfdir,
+        pkgdatadir,
+        pkglibdir,
+        pkgincludedir,
+        pkglibexecdir,
+        destdir,
+        sysroot,
+    }
+}
+
+///
+/// Canonical directories serialize as the underlying [`InstallDirs`].
+/// They cannot be deserialized directly; deserialize an [`InstallDirs`] and canonicalize it instead.
+impl ::serde::ser::Serialize for CanonicalInstallDirs {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ::serde::ser::Serializer,
+    {
+        self.as_install_dirs().serialize(serializer)
     }
 }
@@ -0,0 +1,109 @@
+use std::path::{Path, PathBuf};
+
+use super::{under_root, CanonicalInstallDirs, Dir};
+
+///
+/// A view of canonical install directories under a staging root, such as `DESTDIR`, see [`CanonicalInstallDirs::staged`].
+///
+/// Each directory has a run-time path, which is the canonical path that should be embedded in the installed program,
+///  and an install-time path, which is where files are written to while staging.
+///
+/// ## Example
+///
+/// ```
+/// use install_dirs::dirs::{Dir, InstallDirs};
+/// let mut dirs = InstallDirs::defaults();
+/// dirs.prefix = "/usr".into();
+/// let canonical = dirs.canonicalize().unwrap();
+/// let staged = canonical.staged("/tmp/stage/");
+/// assert_eq!(staged.runtime_path(Dir::Libdir).to_str(), Some("/usr/lib"));
+/// assert_eq!(staged.install_path(Dir::Libdir).to_str(), Some("/tmp/stage/usr/lib"));
+/// assert_eq!(staged.install_path_of("/etc/foo.conf").to_str(), Some("/tmp/stage/etc/foo.conf"));
+/// ```
+#[derive(Clone, Debug)]
+pub struct StagedDirs<'a> {
+    dirs: &'a CanonicalInstallDirs,
+    destdir: Option<PathBuf>,
+}
+
+impl<'a> StagedDirs<'a> {
+    ///
+    /// The staging root, or `None` if files are installed directly to their run-time paths
+    pub fn destdir(&self) -> Option<&Path> {
+        self.destdir.as_deref()
+    }
+
+    ///
+    /// The path of `dir` at run time, which is its canonical value
+    pub fn runtime_path(&self, dir: Dir) -> &'a Path {
+        self.dirs.get(dir)
+    }
+
+    ///
+    /// The path files in `dir` are written to while staging, which is its run-time path under the staging root.
+    /// `manext`, which is not a directory, is returned unchanged.
+    pub fn install_path(&self, dir: Dir) -> PathBuf {
+        if dir.is_path() {
+            self.install_path_of(self.runtime_path(dir))
+        } else {
+            self.runtime_path(dir).to_path_buf()
+        }
+    }
+
+    ///
+    /// Maps any absolute run-time `path`, such as a file within one of the directories, to the path it is written to while staging
+    pub fn install_path_of<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        match &self.destdir {
+            Some(destdir) => under_root(destdir, path.as_ref()),
+            None => path.as_ref().to_path_buf(),
+        }
+    }
+
+    ///
+    /// Obtains an iterator over every directory, its install-time path, and its run-time path, in the order of [`Dir::ALL`]
+    pub fn iter(&self) -> impl Iterator<Item = (Dir, PathBuf, &'a Path)> + '_ {
+        Dir::ALL
+            .iter()
+            .map(move |&dir| (dir, self.install_path(dir), self.runtime_path(dir)))
+    }
+}
+
+impl CanonicalInstallDirs {
+    ///
+    /// Returns a view of these directories staged under `destdir`.
+    /// An empty `destdir` means files are installed directly to their run-time paths, as with an empty `DESTDIR`.
+    pub fn staged<P: Into<PathBuf>>(&self, destdir: P) -> StagedDirs<'_> {
+        let destdir = destdir.into();
+        StagedDirs {
+            dirs: self,
+            destdir: Some(destdir).filter(|destdir| !destdir.as_os_str().is_empty()),
+        }
+    }
+
+    ///
+    /// Returns a view of these directories staged under [`InstallDirs::destdir`](super::InstallDirs::destdir),
+    ///  such as the `DESTDIR` read by [`InstallDirs::read_env`](super::InstallDirs::read_env).
+    /// If it is not set, or is empty, files are installed directly to their run-time paths.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use install_dirs::dirs::InstallDirs;
+    /// let mut dirs = InstallDirs::defaults();
+    /// dirs.destdir = Some("".into());
+    /// let dirs = dirs.canonicalize().unwrap();
+    /// let staged = dirs.staged_in_destdir();
+    /// assert_eq!(staged.destdir(), None);
+    /// assert_eq!(staged.install_path_of("/usr/local/bin/foo").to_str(), Some("/usr/local/bin/foo"));
+    /// ```
+    pub fn staged_in_destdir(&self) -> StagedDirs<'_> {
+        StagedDirs {
+            dirs: self,
+            destdir: self
+                .as_install_dirs()
+                .destdir
+                .clone()
+                .filter(|destdir| !destdir.as_os_str().is_empty()),
+        }
+    }
+}